@@ -0,0 +1,251 @@
+use crate::param;
+use crate::preset;
+use crate::sequencer::{Pattern, STEPS_PER_BEAT};
+use serde_derive::{Deserialize, Serialize};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub type Shared = Arc<Mutex<Automation>>;
+
+// how often a synced loop checks the sequencer's position while waiting for a beat
+const BEAT_POLL: Duration = Duration::from_millis(1);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    pub time: f32,
+    pub value: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recording {
+    pub length: f32,
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    // stretch the loop to a whole number of beats
+    fn quantize(&mut self, bpm: f32) {
+        let beat = 60.0 / bpm;
+        let beats = (self.length / beat).round().max(1.0);
+        let scale = (beats * beat) / self.length;
+
+        for event in self.events.iter_mut() {
+            event.time *= scale;
+        }
+
+        self.length = beats * beat;
+    }
+}
+
+// waits for the sequencer to move onto the start of a beat, straight away if
+// it isn't running. Returns false if the player was stopped while waiting.
+fn wait_for_beat(tempo: &Arc<Mutex<Pattern>>, rx: &mpsc::Receiver<()>) -> bool {
+    let mut last = tempo.lock().unwrap().position;
+
+    loop {
+        let position = tempo.lock().unwrap().position;
+        match position {
+            None => return true,
+            Some(step) if position != last && step % STEPS_PER_BEAT as usize == 0 => return true,
+            _ => {},
+        }
+        last = position;
+
+        if let Err(mpsc::RecvTimeoutError::Disconnected) = rx.recv_timeout(BEAT_POLL) {
+            return false;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Capture {
+    started: Instant,
+    events: Vec<Event>,
+}
+
+#[derive(Debug)]
+struct Player {
+    // dropping the sender stops the player thread
+    tx: mpsc::Sender<()>,
+    thread: thread::JoinHandle<()>,
+}
+
+#[derive(Debug)]
+pub struct Automation {
+    patch: String,
+    param: param::Shared,
+    tempo: Option<Arc<Mutex<Pattern>>>,
+    recording: Option<Recording>,
+    capture: Option<Capture>,
+    player: Option<Player>,
+}
+
+impl Automation {
+    pub fn new(patch: &str, param: param::Shared, tempo: Option<Arc<Mutex<Pattern>>>, recording: Option<Recording>) -> Self {
+        Automation {
+            patch: patch.to_string(),
+            param: param,
+            tempo: tempo,
+            recording: recording,
+            capture: None,
+            player: None,
+        }
+    }
+
+    pub fn shared(self) -> Shared {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.player.is_some()
+    }
+
+    // cycles through recording -> playing -> cleared
+    pub fn toggle(&mut self) {
+        if self.is_recording() {
+            self.finish();
+            self.play();
+        } else if self.recording.is_some() {
+            self.stop();
+            self.recording = None;
+            self.save();
+        } else {
+            self.record();
+        }
+    }
+
+    // replace the recording, e.g. when a preset is reloaded, carrying on
+    // playing if it was
+    pub fn load(&mut self, recording: Option<Recording>) {
+        let playing = self.is_playing();
+        self.stop();
+        self.recording = recording;
+
+        if playing {
+            self.play();
+        }
+    }
+
+    pub fn record(&mut self) {
+        self.stop();
+
+        let value = self.param.lock().unwrap().value;
+        self.capture = Some(Capture {
+            started: Instant::now(),
+            events: vec![Event { time: 0.0, value: value }],
+        });
+    }
+
+    pub fn capture(&mut self, value: f32) {
+        if let Some(capture) = &mut self.capture {
+            let time = capture.started.elapsed().as_secs_f32();
+            capture.events.push(Event { time: time, value: value });
+        }
+    }
+
+    pub fn finish(&mut self) {
+        if let Some(capture) = self.capture.take() {
+            let mut recording = Recording {
+                length: capture.started.elapsed().as_secs_f32(),
+                events: capture.events,
+            };
+
+            if let Some(tempo) = &self.tempo {
+                recording.quantize(tempo.lock().unwrap().bpm);
+            }
+
+            self.recording = Some(recording);
+            self.save();
+        }
+    }
+
+    pub fn play(&mut self) {
+        if self.player.is_some() {
+            return;
+        }
+
+        let recording = match &self.recording {
+            Some(recording) if recording.length > 0.0 => recording.clone(),
+            _ => return,
+        };
+
+        let param = self.param.clone();
+        let tempo = self.tempo.clone();
+        let (tx, rx) = mpsc::channel();
+
+        let thread = thread::spawn(move || {
+            loop {
+                // synced loops start on the sequencer's beat so they stay in
+                // phase with it
+                if let Some(tempo) = &tempo {
+                    if !wait_for_beat(tempo, &rx) {
+                        return;
+                    }
+                }
+
+                let started = Instant::now();
+
+                for event in recording.events.iter() {
+                    let at = Duration::from_secs_f32(event.time);
+                    let wait = at.checked_sub(started.elapsed()).unwrap_or_default();
+
+                    if let Err(mpsc::RecvTimeoutError::Disconnected) = rx.recv_timeout(wait) {
+                        return;
+                    }
+
+                    let mut param = param.lock().unwrap();
+                    param.value = param.clamp(event.value);
+                    param.send();
+                }
+
+                // end a synced loop half a beat early and wait for the beat
+                // instead, rather than drifting from the sequencer's clock
+                let mut end = Duration::from_secs_f32(recording.length);
+                if let Some(tempo) = &tempo {
+                    let tempo = tempo.lock().unwrap();
+                    if tempo.position.is_some() {
+                        let half_beat = Duration::from_secs_f32(30.0 / tempo.bpm);
+                        end = end.checked_sub(half_beat).unwrap_or_default();
+                    }
+                }
+
+                let wait = end.checked_sub(started.elapsed()).unwrap_or_default();
+
+                if let Err(mpsc::RecvTimeoutError::Disconnected) = rx.recv_timeout(wait) {
+                    return;
+                }
+            }
+        });
+
+        self.player = Some(Player { tx: tx, thread: thread });
+    }
+
+    pub fn stop(&mut self) {
+        self.capture = None;
+
+        if let Some(player) = self.player.take() {
+            drop(player.tx);
+            player.thread.join().unwrap();
+        }
+    }
+
+    fn save(&self) {
+        let name = self.param.lock().unwrap().name.clone();
+        let mut preset = preset::load(&self.patch).unwrap_or_default();
+
+        match &self.recording {
+            Some(recording) => { preset.automations.insert(name, recording.clone()); },
+            None => { preset.automations.remove(&name); },
+        }
+
+        if let Err(err) = preset::save(&self.patch, &preset) {
+            println!("error saving preset: {}", err);
+        }
+    }
+}
@@ -1,7 +1,15 @@
+use crate::modulation;
+use crate::osc;
+use crate::param;
+use crate::synthdefs::format::{self, SynthDef};
+use crate::template::{self, Template};
 use serde_derive::Deserialize;
 use state;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::io;
+use std::path::Path;
+use std::time::Duration;
 use toml;
 
 pub static CONFIG: state::Storage<Config> = state::Storage::new();
@@ -10,6 +18,11 @@ pub static CONFIG: state::Storage<Config> = state::Storage::new();
 pub struct Config {
     pub devices: Devices,
     pub osc: Osc,
+    pub presets: Option<Presets>,
+    pub remote: Option<Remote>,
+    #[serde(default)]
+    pub display: Display,
+    pub server: Option<Server>,
     pub menus: Vec<Menu>,
 }
 
@@ -22,32 +35,821 @@ pub struct Devices {
 
 #[derive(Deserialize, Debug)]
 pub struct Osc {
+    pub addr: Option<String>,
+    #[serde(default)]
+    pub targets: BTreeMap<String, String>,
+    pub default: Option<Vec<String>>,
+    #[serde(default)]
+    pub transport: osc::Transport,
+    #[serde(default)]
+    pub framing: osc::Framing,
+    #[serde(default = "default_control_rate")]
+    pub control_rate: f32,
+    pub latency: Option<f32>,
+    #[serde(default = "default_max_rate")]
+    pub max_rate: f32,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+}
+
+impl Osc {
+    // `addr` is shorthand for a target named "default"
+    fn target(&self, name: &str) -> Option<&String> {
+        match (name, &self.addr) {
+            ("default", Some(addr)) => Some(addr),
+            _ => self.targets.get(name),
+        }
+    }
+
+    // how far ahead bundles of related changes are scheduled
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency.map(Duration::from_secs_f32)
+    }
+
+    fn parse_target(&self, name: &str) -> Result<osc::Target, String> {
+        let addr = self.target(name).ok_or_else(|| format!("unknown OSC target {}", name))?;
+        osc::Target::parse(addr, self.transport, self.framing)
+            .map_err(|err| format!("OSC target {}: {}", name, err))
+    }
+
+    // resolve target names, falling back to the default targets
+    pub fn resolve(&self, names: Option<&Vec<String>>) -> Vec<osc::Target> {
+        let fallback = vec!["default".to_string()];
+        let names = names.or(self.default.as_ref()).unwrap_or(&fallback);
+
+        names.iter().filter_map(|name| self.parse_target(name).ok()).collect()
+    }
+
+    fn validate(&self, names: Option<&Vec<String>>) -> Result<(), String> {
+        let fallback = vec!["default".to_string()];
+        let names = names.or(self.default.as_ref()).unwrap_or(&fallback);
+
+        for name in names {
+            self.parse_target(name)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn default_control_rate() -> f32 {
+    20.0
+}
+
+fn default_poll_interval() -> f32 {
+    1.0
+}
+
+fn default_timeout() -> f32 {
+    3.0
+}
+
+fn default_log() -> String {
+    "server.log".to_string()
+}
+
+fn default_max_rate() -> f32 {
+    500.0
+}
+
+fn default_queue_size() -> usize {
+    256
+}
+
+fn default_font_size() -> f32 {
+    14.0
+}
+
+fn default_max_fps() -> f32 {
+    30.0
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Presets {
+    pub dir: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Server {
+    pub addr: String,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: f32,
+    #[serde(default = "default_timeout")]
+    pub timeout: f32,
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_log")]
+    pub log: String,
+    #[serde(default)]
+    pub synthdefs: Vec<String>,
+}
+
+impl Server {
+    // where /status and synthdefs go, over the transport set in [osc]
+    pub fn target(&self, osc: &Osc) -> Result<osc::Target, String> {
+        let target = osc::Target::parse(&self.addr, osc.transport, osc.framing)
+            .map_err(|err| format!("server addr: {}", err))?;
+
+        if target.transport == osc::Transport::Unix {
+            return Err("server addr can't be a unix socket, replies can't come back over one".to_string());
+        }
+
+        Ok(target)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Display {
+    // whether menus wrap around from the last item to the first
+    #[serde(default)]
+    pub wrap: bool,
+    // a BDF, PCF or TrueType/OpenType font to use instead of the built-in one
+    pub font: Option<String>,
+    // fonts to take glyphs from when the font doesn't have them
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
+    // point size of outline fonts, bitmap fonts have a fixed size
+    #[serde(default = "default_font_size")]
+    pub size: f32,
+    // extra pixels between lines
+    #[serde(default)]
+    pub line_spacing: f32,
+    #[serde(default)]
+    pub margins: Margins,
+    // the screen isn't rendered more often than this, and only when something changed
+    #[serde(default = "default_max_fps")]
+    pub max_fps: f32,
+    // draw into an offscreen buffer and pan to it, on drivers that can
+    #[serde(default)]
+    pub double_buffer: bool,
+    // degrees clockwise to turn the picture, for displays that aren't mounted upright
+    #[serde(default)]
+    pub rotation: u32,
+    // mirror the picture left to right
+    #[serde(default)]
+    pub flip: bool,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display {
+            wrap: false,
+            font: None,
+            fallbacks: vec![],
+            size: default_font_size(),
+            line_spacing: 0.0,
+            margins: Margins::default(),
+            max_fps: default_max_fps(),
+            double_buffer: false,
+            rotation: 0,
+            flip: false,
+        }
+    }
+}
+
+// space in pixels between the edges of the screen and the text
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Remote {
     pub addr: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Menu {
     pub name: String,
-    pub patches: Vec<Patch>,
+    // `patches` and `actions` are moved into `items` by `normalize`
+    #[serde(default)]
+    patches: Vec<Patch>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default)]
+    actions: Vec<Action>,
+}
+
+impl Menu {
+    // `items` is shown in the order it's written, the older `patches` and
+    // `actions` lists go before and after it
+    fn normalize(&mut self) {
+        let mut items = self.patches.drain(..).map(Item::Patch).collect::<Vec<Item>>();
+        items.append(&mut self.items);
+        items.extend(self.actions.drain(..).map(Item::Action));
+
+        for item in items.iter_mut() {
+            item.normalize();
+        }
+
+        self.items = items;
+    }
+
+    fn patches_mut<'a>(&'a mut self, patches: &mut Vec<&'a mut Patch>) {
+        for item in self.items.iter_mut() {
+            match item {
+                Item::Menu(menu) => menu.patches_mut(patches),
+                Item::Patch(patch) => patches.push(patch),
+                _ => {},
+            }
+        }
+    }
+
+    // the text this menu and everything in it puts on the screen
+    fn labels<'a>(&'a self, labels: &mut Vec<&'a str>) {
+        labels.push(&self.name);
+
+        for item in self.items.iter() {
+            item.labels(labels);
+        }
+    }
+
+    // `patch` is the patch this menu is part of, if any
+    fn validate(&self, osc: &Osc, patch: Option<&Patch>) -> Result<(), String> {
+        validate_name("menu", &self.name)?;
+
+        for item in self.items.iter() {
+            item.validate(osc, patch)?;
+        }
+
+        Ok(())
+    }
+}
+
+// an entry in a menu, any of which can appear at any depth except for
+// params, which belong to the patch they're in. The kind of item is given by
+// `item` since params already use `type` for their kind of value.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "item", rename_all = "lowercase")]
+pub enum Item {
+    Menu(Menu),
+    Patch(Patch),
+    Param(Param),
+    Action(Action),
+    Screen(Screen),
+}
+
+impl Item {
+    fn normalize(&mut self) {
+        match self {
+            Item::Menu(menu) => menu.normalize(),
+            Item::Patch(patch) => patch.normalize(),
+            _ => {},
+        }
+    }
+
+    fn params<'a>(&'a self, params: &mut Vec<&'a Param>) {
+        match self {
+            Item::Menu(menu) => {
+                for item in menu.items.iter() {
+                    item.params(params);
+                }
+            },
+            Item::Param(param) => params.push(param),
+            _ => {},
+        }
+    }
+
+    fn labels<'a>(&'a self, labels: &mut Vec<&'a str>) {
+        match self {
+            Item::Menu(menu) => menu.labels(labels),
+            Item::Patch(patch) => patch.labels(labels),
+            Item::Param(param) => param.labels(labels),
+            Item::Action(action) => labels.push(&action.name),
+            Item::Screen(screen) => labels.push(&screen.name),
+        }
+    }
+
+    fn validate(&self, osc: &Osc, patch: Option<&Patch>) -> Result<(), String> {
+        match (self, patch) {
+            (Item::Menu(menu), _) => menu.validate(osc, patch),
+            (Item::Patch(inner), None) => inner.validate(osc),
+            (Item::Patch(inner), Some(patch)) => {
+                Err(format!("patch {} can't be inside patch {}", inner.name, patch.name))
+            },
+            // params are validated along with the rest of their patch's
+            (Item::Param(_), Some(_)) => Ok(()),
+            (Item::Param(param), None) => Err(format!("param {} isn't inside a patch", param.name)),
+            (Item::Action(action), _) => {
+                let targets = patch.and_then(|patch| patch.targets.as_ref());
+                osc.validate(action.targets.as_ref().or(targets))
+                    .map_err(|err| format!("action {}: {}", action.name, err))
+            },
+            (Item::Screen(_), _) => Ok(()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltIn {
+    Status,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Screen {
+    pub name: String,
+    pub screen: BuiltIn,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Patch {
     pub name: String,
-    pub params: Vec<Param>,
+    // `params` and `actions` are moved into `items` by `normalize`
+    #[serde(default)]
+    params: Vec<Param>,
+    pub synthdef: Option<String>,
+    #[serde(default)]
+    pub overrides: BTreeMap<String, Override>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    pub sequencer: Option<Sequencer>,
+    #[serde(default)]
+    actions: Vec<Action>,
+    pub osc: Option<PatchOsc>,
+    pub targets: Option<Vec<String>>,
+}
+
+impl Patch {
+    // like `Menu::normalize`, `params` go first and `actions` last
+    fn normalize(&mut self) {
+        let mut items = self.params.drain(..).map(Item::Param).collect::<Vec<Item>>();
+        items.append(&mut self.items);
+        items.extend(self.actions.drain(..).map(Item::Action));
+
+        for item in items.iter_mut() {
+            item.normalize();
+        }
+
+        self.items = items;
+    }
+
+    // every param in its items, including submenus
+    pub fn all_params(&self) -> Vec<&Param> {
+        let mut params = vec![];
+
+        for item in self.items.iter() {
+            item.params(&mut params);
+        }
+
+        params
+    }
+
+    fn labels<'a>(&'a self, labels: &mut Vec<&'a str>) {
+        labels.push(&self.name);
+
+        for item in self.items.iter() {
+            item.labels(labels);
+        }
+    }
+
+    // one float param per control, in the order they're declared
+    fn generate_params(&mut self, defs: &[SynthDef]) {
+        let def_name = self.synthdef.as_ref().unwrap_or(&self.name);
+
+        let def = match defs.iter().find(|def| &def.name == def_name) {
+            Some(def) => def,
+            None => {
+                println!("patch {} has no params and no synthdef {} was found", self.name, def_name);
+                return;
+            },
+        };
+
+        for name in self.overrides.keys() {
+            if !def.controls.iter().any(|control| &control.name == name) {
+                println!("synthdef {} has no control {} to override", def.name, name);
+            }
+        }
+
+        let overrides = &self.overrides;
+        let params = def.controls.iter().filter_map(|control| {
+            let override_config = overrides.get(&control.name);
+
+            if override_config.map_or(false, |o| o.hide) {
+                None
+            } else {
+                Some(Item::Param(Param::generated(&control.name, control.value, override_config)))
+            }
+        }).collect::<Vec<Item>>();
+
+        self.items.splice(0..0, params);
+    }
+
+    fn validate(&self, osc: &Osc) -> Result<(), String> {
+        validate_name("patch", &self.name)?;
+
+        if let Some(osc_config) = &self.osc {
+            let messages = vec![
+                (&osc_config.start, template::PATCH_PLACEHOLDERS),
+                (&osc_config.stop, template::PATCH_PLACEHOLDERS),
+                (&osc_config.set, template::PARAM_PLACEHOLDERS),
+                (&osc_config.note, template::NOTE_PLACEHOLDERS),
+            ];
+
+            for (message, allowed) in messages {
+                if let Some(message) = message {
+                    Template::parse(message, allowed)
+                        .map_err(|err| format!("patch {}: {}", self.name, err))?;
+                }
+            }
+        }
+
+        osc.validate(self.targets.as_ref())
+            .map_err(|err| format!("patch {}: {}", self.name, err))?;
+
+        if let Some(sequencer) = &self.sequencer {
+            if sequencer.steps == 0 {
+                return Err(format!("patch {}: sequencer steps must be more than 0", self.name));
+            }
+
+            if !sequencer.bpm.is_finite() || sequencer.bpm <= 0.0 {
+                return Err(format!("patch {}: sequencer bpm must be more than 0", self.name));
+            }
+        }
+
+        let mut names = BTreeSet::new();
+        for param in self.all_params() {
+            if !names.insert(&param.name) {
+                return Err(format!("patch {}: param {} is listed more than once", self.name, param.name));
+            }
+
+            param.validate()?;
+            osc.validate(param.targets.as_ref().or(self.targets.as_ref()))
+                .map_err(|err| format!("param {}: {}", param.name, err))?;
+        }
+
+        for item in self.items.iter() {
+            item.validate(osc, Some(self))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Override {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub step: Option<f32>,
+    #[serde(default)]
+    pub hide: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PatchOsc {
+    pub start: Option<Message>,
+    pub stop: Option<Message>,
+    pub set: Option<Message>,
+    pub note: Option<Message>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Message {
+    pub addr: String,
+    #[serde(default)]
+    pub args: Vec<Arg>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Action {
+    pub name: String,
+    pub addr: String,
+    #[serde(default)]
+    pub args: Vec<Arg>,
+    #[serde(default)]
+    pub momentary: bool,
+    pub targets: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    String(String),
+}
+
+impl Arg {
+    pub fn to_osc(&self) -> osc::Type {
+        match self {
+            Arg::Int(value) => osc::Type::Int(*value),
+            Arg::Float(value) => osc::Type::Float(*value),
+            Arg::Bool(value) => osc::Type::Bool(*value),
+            Arg::String(value) => osc::Type::String(value.clone()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Sequencer {
+    pub steps: usize,
+    pub bpm: f32,
+    pub params: Vec<String>,
+    #[serde(default)]
+    pub notes: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    Float,
+    Int,
+    Toggle,
+    Choice,
+}
+
+impl Default for ParamType {
+    fn default() -> Self {
+        ParamType::Float
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Number(f32),
+    Label(String),
+}
+
+// a choice is either sent as a symbol (its label) or mapped to a number
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Choice {
+    Symbol(String),
+    Value {
+        label: String,
+        value: f32,
+    },
+}
+
+impl Choice {
+    pub fn label(&self) -> &str {
+        match self {
+            Choice::Symbol(label) => label,
+            Choice::Value { label, .. } => label,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Param {
     pub name: String,
-    pub value: f32,
-    pub step: f32,
-    pub min: f32,
-    pub max: f32,
+    #[serde(rename = "type", default)]
+    pub param_type: ParamType,
+    pub value: Value,
+    pub step: Option<f32>,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+    pub lfo: Option<Lfo>,
+    pub automation: Option<Automation>,
+    pub osc: Option<Message>,
+    pub targets: Option<Vec<String>>,
+}
+
+impl Param {
+    fn labels<'a>(&'a self, labels: &mut Vec<&'a str>) {
+        labels.push(&self.name);
+        labels.extend(self.unit.iter().map(|unit| unit.as_str()));
+        labels.extend(self.choices.iter().map(|choice| choice.label()));
+    }
+
+    // a param for a SynthDef control, without overrides it ranges from zero
+    // to twice the default (or 0..1 when the default is zero)
+    fn generated(name: &str, value: f32, override_config: Option<&Override>) -> Self {
+        let (min, max) = if value > 0.0 {
+            (0.0, value * 2.0)
+        } else if value < 0.0 {
+            (value * 2.0, -value * 2.0)
+        } else {
+            (0.0, 1.0)
+        };
+
+        let min = override_config.and_then(|o| o.min).unwrap_or(min);
+        let max = override_config.and_then(|o| o.max).unwrap_or(max);
+        let step = override_config.and_then(|o| o.step).unwrap_or((max - min) / 100.0);
+
+        Param {
+            name: name.to_string(),
+            param_type: ParamType::Float,
+            value: Value::Number(value.max(min).min(max)),
+            step: Some(step),
+            min: Some(min),
+            max: Some(max),
+            unit: None,
+            choices: vec![],
+            lfo: None,
+            automation: None,
+            osc: None,
+            targets: None,
+        }
+    }
+
+    pub fn kind(&self) -> param::Kind {
+        match self.param_type {
+            ParamType::Float => param::Kind::Float,
+            ParamType::Int => param::Kind::Int,
+            ParamType::Toggle => param::Kind::Toggle,
+            ParamType::Choice => param::Kind::Choice(self.choices.clone()),
+        }
+    }
+
+    // the accessors below assume the param passed `validate`
+
+    pub fn value(&self) -> f32 {
+        match &self.value {
+            Value::Bool(value) => if *value { 1.0 } else { 0.0 },
+            // kept in range, and whole for ints and choice indexes
+            Value::Number(value) => match self.param_type {
+                ParamType::Float => value.max(self.min()).min(self.max()),
+                ParamType::Int => value.round().max(self.min()).min(self.max()),
+                _ => value.round(),
+            },
+            Value::Label(label) => {
+                self.choices.iter().position(|c| c.label() == label).unwrap() as f32
+            },
+        }
+    }
+
+    pub fn step(&self) -> f32 {
+        match self.param_type {
+            ParamType::Float => self.step.unwrap(),
+            ParamType::Int => self.step.unwrap_or(1.0).round(),
+            _ => 1.0,
+        }
+    }
+
+    pub fn min(&self) -> f32 {
+        match self.param_type {
+            ParamType::Float | ParamType::Int => self.min.unwrap(),
+            _ => 0.0,
+        }
+    }
+
+    pub fn max(&self) -> f32 {
+        match self.param_type {
+            ParamType::Float | ParamType::Int => self.max.unwrap(),
+            ParamType::Toggle => 1.0,
+            ParamType::Choice => (self.choices.len() - 1) as f32,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_name("param", &self.name)?;
+
+        match self.param_type {
+            ParamType::Float | ParamType::Int => {
+                match (self.min, self.max) {
+                    (Some(min), Some(max)) if min < max => {},
+                    (Some(_), Some(_)) => return Err(format!("param {} needs a min less than its max", self.name)),
+                    _ => return Err(format!("param {} needs a min and max", self.name)),
+                }
+                if self.param_type == ParamType::Float && self.step.is_none() {
+                    return Err(format!("param {} needs a step", self.name));
+                }
+            },
+            ParamType::Toggle => {},
+            ParamType::Choice => {
+                if self.choices.is_empty() {
+                    return Err(format!("param {} needs at least one choice", self.name));
+                }
+            },
+        }
+
+        if let Some(message) = &self.osc {
+            Template::parse(message, template::PARAM_PLACEHOLDERS)
+                .map_err(|err| format!("param {}: {}", self.name, err))?;
+        }
+
+        match (&self.value, self.param_type) {
+            (Value::Bool(_), ParamType::Toggle) => Ok(()),
+            (Value::Number(_), ParamType::Float) | (Value::Number(_), ParamType::Int) => Ok(()),
+            (Value::Number(value), ParamType::Choice) if value.round() >= 0.0 && (value.round() as usize) < self.choices.len() => Ok(()),
+            (Value::Label(label), ParamType::Choice) if self.choices.iter().any(|c| c.label() == label) => Ok(()),
+            _ => Err(format!("param {} has an invalid value", self.name)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Lfo {
+    pub shape: modulation::Shape,
+    pub rate: f32,
+    pub depth: f32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Automation {
+    #[serde(default)]
+    pub sync: bool,
+}
+
+// menus, patches and params are addressed by their path, so a name can't
+// contain the separator
+fn validate_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.contains('/') {
+        return Err(format!("{} {} can't contain '/'", kind, name));
+    }
+
+    Ok(())
+}
+
+fn validate(config: &Config) -> Result<(), String> {
+    if config.osc.control_rate.is_nan() || config.osc.control_rate <= 0.0 {
+        return Err("osc control_rate must be more than 0".to_string());
+    }
+
+    if config.osc.max_rate.is_nan() || config.osc.max_rate <= 0.0 {
+        return Err("osc max_rate must be more than 0".to_string());
+    }
+
+    if config.osc.queue_size == 0 {
+        return Err("osc queue_size must be more than 0".to_string());
+    }
+
+    if config.display.max_fps.is_nan() || config.display.max_fps <= 0.0 {
+        return Err("display max_fps must be more than 0".to_string());
+    }
+
+    if ![0, 90, 180, 270].contains(&config.display.rotation) {
+        return Err(format!("display rotation {} isn't 0, 90, 180 or 270", config.display.rotation));
+    }
+
+    if let Some(server) = &config.server {
+        if server.poll_interval.is_nan() || server.poll_interval <= 0.0 {
+            return Err("server poll_interval must be more than 0".to_string());
+        }
+
+        if server.timeout.is_nan() || server.timeout <= 0.0 {
+            return Err("server timeout must be more than 0".to_string());
+        }
+
+        server.target(&config.osc)?;
+    }
+
+    for menu in config.menus.iter() {
+        menu.validate(&config.osc, None)?;
+    }
+
+    Ok(())
+}
+
+// every name, unit and choice from the menus that gets drawn on the screen
+pub fn labels(config: &Config) -> Vec<&str> {
+    let mut labels = vec![];
+    for menu in config.menus.iter() {
+        menu.labels(&mut labels);
+    }
+
+    labels
+}
+
+// fill in the params of patches that don't list any from their SynthDefs
+fn generate_params(config: &mut Config) {
+    let paths = config.server.as_ref().map_or(vec![], |server| server.synthdefs.clone());
+    let mut patches = vec![];
+    for menu in config.menus.iter_mut() {
+        menu.patches_mut(&mut patches);
+    }
+    patches.retain(|patch| patch.all_params().is_empty());
+
+    if patches.is_empty() || paths.is_empty() {
+        return;
+    }
+
+    let mut defs = vec![];
+    for path in paths.iter() {
+        match format::read(Path::new(path)) {
+            Ok(read) => defs.extend(read),
+            Err(err) => println!("error reading synthdefs from {}: {}", path, err),
+        }
+    }
+
+    for patch in patches {
+        patch.generate_params(&defs);
+    }
 }
 
 pub fn parse(path: &str) -> Result<Config, io::Error> {
     let config_toml = fs::read_to_string(path)?;
-    let config: Config = toml::from_str(&config_toml)?;
+    let mut config: Config = toml::from_str(&config_toml)?;
+
+    for menu in config.menus.iter_mut() {
+        menu.normalize();
+    }
+
+    generate_params(&mut config);
+
+    validate(&config).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
     Ok(config)
 }
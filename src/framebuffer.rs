@@ -8,9 +8,16 @@ use std::io;
 use std::mem;
 use std::path::{Path};
 use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+use state;
 
 const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+const FBIOPUT_VSCREENINFO: libc::c_ulong = 0x4601;
 const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+const FBIOPAN_DISPLAY: libc::c_ulong = 0x4606;
+
+// the screen info from before double buffering changed it, put back on exit
+static ORIGINAL: state::Storage<Mutex<Option<(File, VarScreenInfo)>>> = state::Storage::new();
 
 #[repr(C)]
 #[derive(Clone, Debug)]
@@ -99,6 +106,13 @@ pub struct Framebuffer {
     pub frame: MmapMut,
     pub var_screen_info: VarScreenInfo,
     pub fix_screen_info: FixScreenInfo,
+    // what was last drawn into each buffer, so only changed pixels get written
+    drawn: Vec<Option<Vec<u32>>>,
+    // the buffer on screen
+    current: usize,
+    // degrees clockwise, and whether to mirror before rotating
+    rotation: u32,
+    flip: bool,
 }
 
 #[derive(Debug)]
@@ -171,20 +185,112 @@ fn get_fix_screen_info(device: &File) -> Result<FixScreenInfo, FramebufferError>
     }
 }
 
+fn put_var_screen_info(device: &File, info: &VarScreenInfo) -> Result<(), FramebufferError> {
+    let result = unsafe {
+        ioctl(device.as_raw_fd(), FBIOPUT_VSCREENINFO, info)
+    };
+
+    match result {
+        -1 => {
+            let err = FramebufferError::new(FramebufferErrorKind::IoctlFailed, "ioctl failed");
+            Err(err)
+        },
+        _ => Ok(()),
+    }
+}
+
+fn pan_display(device: &File, info: &VarScreenInfo) -> Result<(), FramebufferError> {
+    let result = unsafe {
+        ioctl(device.as_raw_fd(), FBIOPAN_DISPLAY, info)
+    };
+
+    match result {
+        -1 => {
+            let err = FramebufferError::new(FramebufferErrorKind::IoctlFailed, "pan failed");
+            Err(err)
+        },
+        _ => Ok(()),
+    }
+}
+
+// ask for a virtual screen twice as tall, the driver may ignore it, not
+// support panning between the halves or not have the memory for both
+fn enable_double_buffer(device: &File, info: &VarScreenInfo) -> Result<VarScreenInfo, FramebufferError> {
+    let mut wanted = info.clone();
+    wanted.yres_virtual = info.yres * 2;
+    wanted.yoffset = 0;
+    put_var_screen_info(device, &wanted)?;
+
+    let checked = get_var_screen_info(device).and_then(|actual| {
+        let fix_screen_info = get_fix_screen_info(device)?;
+
+        if actual.yres_virtual < actual.yres * 2 || fix_screen_info.ypanstep == 0 {
+            return Err(FramebufferError::new(FramebufferErrorKind::IoctlFailed, "driver can't pan"));
+        }
+
+        if frame_len(&actual, &fix_screen_info, 2) > fix_screen_info.smem_len {
+            return Err(FramebufferError::new(FramebufferErrorKind::IoError, "not enough framebuffer memory"));
+        }
+
+        Ok(actual)
+    });
+
+    match checked {
+        Ok(actual) => {
+            if let Ok(original) = device.try_clone() {
+                ORIGINAL.set(Mutex::new(Some((original, info.clone()))));
+            }
+            Ok(actual)
+        },
+        Err(err) => {
+            put_var_screen_info(device, info)?;
+            Err(err)
+        },
+    }
+}
+
+// put back the screen info double buffering changed, if it did
+pub fn restore() {
+    let original = ORIGINAL.try_get().and_then(|original| original.lock().unwrap().take());
+
+    if let Some((device, info)) = original {
+        if let Err(err) = put_var_screen_info(&device, &info) {
+            println!("error restoring framebuffer: {}", err);
+        }
+    }
+}
+
 impl Framebuffer {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Framebuffer, FramebufferError> {
+    pub fn new<P: AsRef<Path>>(path: P, double_buffer: bool) -> Result<Framebuffer, FramebufferError> {
         let device = OpenOptions::new()
             .read(true)
             .write(true)
             .open(path)?;
 
-        let var_screen_info = get_var_screen_info(&device)?;
+        let mut var_screen_info = get_var_screen_info(&device)?;
+        let mut buffers = 1;
+
+        if double_buffer {
+            match enable_double_buffer(&device, &var_screen_info) {
+                Ok(info) => {
+                    var_screen_info = info;
+                    buffers = 2;
+                },
+                Err(err) => println!("error enabling double buffering: {}", err),
+            }
+        }
+
         let fix_screen_info = get_fix_screen_info(&device)?;
 
-        let xres = var_screen_info.xres;
-        let yres = var_screen_info.yres;
-        let byte_depth = var_screen_info.bits_per_pixel / 8;
-        let frame_len = xres * yres * byte_depth;
+        // the map can't go past the framebuffer's memory, when it's known
+        let frame_len = frame_len(&var_screen_info, &fix_screen_info, buffers);
+        if fix_screen_info.smem_len > 0 && frame_len > fix_screen_info.smem_len {
+            restore();
+            return Err(FramebufferError::new(
+                FramebufferErrorKind::IoError,
+                "frame is larger than the framebuffer memory",
+            ));
+        }
 
         let frame = unsafe {
             MmapOptions::new().len(frame_len as usize).map_mut(&device)
@@ -196,29 +302,149 @@ impl Framebuffer {
                 frame: result,
                 var_screen_info: var_screen_info,
                 fix_screen_info: fix_screen_info,
+                drawn: vec![None; buffers as usize],
+                current: 0,
+                rotation: 0,
+                flip: false,
             }),
-            Err(_) => Err(FramebufferError::new(
-                FramebufferErrorKind::IoError,
-                "mmap failed",
-            ))
+            Err(_) => {
+                restore();
+                Err(FramebufferError::new(
+                    FramebufferErrorKind::IoError,
+                    "mmap failed",
+                ))
+            },
         }
     }
 
-    pub fn draw(&mut self, data: &[u32]) {
+    // for displays mounted upside down or on their side, `rotation` is 0, 90,
+    // 180 or 270 degrees clockwise and `flip` mirrors left to right
+    pub fn orient(&mut self, rotation: u32, flip: bool) {
+        self.rotation = rotation;
+        self.flip = flip;
+        self.drawn = vec![None; self.drawn.len()];
+    }
+
+    // the width and height to draw at, swapped when the display is on its side
+    pub fn size(&self) -> (u32, u32) {
         let xres = self.var_screen_info.xres;
         let yres = self.var_screen_info.yres;
+
+        match self.rotation {
+            90 | 270 => (yres, xres),
+            _ => (xres, yres),
+        }
+    }
+
+    // the frame as the display's pixels are laid out
+    fn physical(&self, data: &[u32]) -> Vec<u32> {
+        let xres = self.var_screen_info.xres as usize;
+        let (width, height) = self.size();
+        let (width, height) = (width as usize, height as usize);
+        let mut physical = vec![0; data.len()];
+
+        for y in 0..height {
+            for x in 0..width {
+                let x_flipped = if self.flip { width - 1 - x } else { x };
+
+                let (px, py) = match self.rotation {
+                    90 => (height - 1 - y, x_flipped),
+                    180 => (width - 1 - x_flipped, height - 1 - y),
+                    270 => (y, width - 1 - x_flipped),
+                    _ => (x_flipped, y),
+                };
+
+                physical[py * xres + px] = data[y * width + x];
+            }
+        }
+
+        physical
+    }
+
+    // with double buffering the frame is drawn into the buffer that's not on
+    // screen, which is then panned to
+    pub fn draw(&mut self, data: &[u32]) {
+        let rotated;
+        let data = if self.rotation == 0 && !self.flip {
+            data
+        } else {
+            rotated = self.physical(data);
+            &rotated
+        };
+
+        if self.drawn[self.current].as_ref().map_or(false, |drawn| drawn.as_slice() == data) {
+            return;
+        }
+
+        let xres = self.var_screen_info.xres as usize;
+        let yres = self.var_screen_info.yres as usize;
         let byte_depth = (self.var_screen_info.bits_per_pixel / 8) as usize;
+        let line_length = line_length(&self.var_screen_info, &self.fix_screen_info) as usize;
+
+        let back = (self.current + 1) % self.drawn.len();
+        let base = back * yres * line_length;
+        let previous = self.drawn[back].take();
 
-        for x in 0..xres {
-            for y in 0..yres {
-                let index = (y * xres + x) as usize;
+        for y in 0..yres {
+            let row = &data[y * xres..(y + 1) * xres];
 
-                if data[index] > 0 {
-                    self.frame[index * byte_depth] = 1;
+            // only the part of the row that changed since this buffer was drawn
+            let (start, end) = match &previous {
+                Some(previous) => match changed(&previous[y * xres..(y + 1) * xres], row) {
+                    Some(span) => span,
+                    None => continue,
+                },
+                None => (0, xres),
+            };
+
+            for x in start..end {
+                let offset = base + y * line_length + x * byte_depth;
+
+                if row[x] > 0 {
+                    self.frame[offset] = 1;
                 } else {
-                    self.frame[index * byte_depth] = 0;
+                    self.frame[offset] = 0;
                 }
             }
         }
+
+        self.drawn[back] = Some(data.to_vec());
+
+        if back != self.current {
+            self.var_screen_info.yoffset = (back * yres) as u32;
+
+            if let Err(err) = pan_display(&self.device, &self.var_screen_info) {
+                println!("error panning display: {}", err);
+            }
+        }
+
+        self.current = back;
     }
 }
+
+// bytes per row, which drivers can pad past the visible width
+fn line_length(var_screen_info: &VarScreenInfo, fix_screen_info: &FixScreenInfo) -> u32 {
+    match fix_screen_info.line_length {
+        0 => var_screen_info.xres * var_screen_info.bits_per_pixel / 8,
+        line_length => line_length,
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// bytes needed for `buffers` whole frames
+fn frame_len(var_screen_info: &VarScreenInfo, fix_screen_info: &FixScreenInfo, buffers: u32) -> u32 {
+    line_length(var_screen_info, fix_screen_info) * var_screen_info.yres * buffers
+}
+
+// the first and one past the last pixel that went on or off between two rows
+fn changed(previous: &[u32], row: &[u32]) -> Option<(usize, usize)> {
+    let differs = |x: &usize| (previous[*x] > 0) != (row[*x] > 0);
+    let start = (0..row.len()).find(differs)?;
+    let end = (0..row.len()).rev().find(differs)? + 1;
+    Some((start, end))
+}
@@ -22,6 +22,8 @@ pub struct InputEvent {
     pub value: i32,
 }
 
+pub const EV_KEY: u16 = 0x01;
+
 const INPUT_EVENT_SIZE: usize = mem::size_of::<InputEvent>();
 
 #[derive(Debug)]
@@ -3,37 +3,82 @@
 #[macro_use]
 extern crate failure;
 
+mod automation;
 mod config;
 mod framebuffer;
 mod input;
+mod modulation;
 mod osc;
+mod param;
+mod preset;
+mod remote;
+mod sequencer;
+mod server;
+mod status;
+mod synthdefs;
+mod template;
+mod trigger;
 mod ui;
 
 use crate::framebuffer::Framebuffer;
-use crate::input::InputDevice;
+use crate::input::{InputDevice, EV_KEY};
 use crate::ui::build_ui;
 use clap::{Arg, App};
 use raqote;
-use std::sync::mpsc;
+use std::mem;
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
+use std::time::{Duration, Instant};
 
-fn ui_loop(rx: mpsc::Receiver<ui::Input>) {
+const LONG_PRESS: Duration = Duration::from_millis(600);
+
+fn ui_loop(rx: mpsc::Receiver<ui::Event>) {
     let conf = &config::CONFIG.get();
 
-    match Framebuffer::new(&conf.devices.framebuffer) {
+    match Framebuffer::new(&conf.devices.framebuffer, conf.display.double_buffer) {
         Ok(mut fb) => {
             let mut ui = build_ui(&conf.menus);
-            let mut target = raqote::DrawTarget::new(
-                fb.var_screen_info.xres as i32,
-                fb.var_screen_info.yres as i32,
-            );
+            fb.orient(conf.display.rotation, conf.display.flip);
+
+            let (width, height) = fb.size();
+            let mut target = raqote::DrawTarget::new(width as i32, height as i32);
+
+            let frame = Duration::from_secs_f32(1.0 / conf.display.max_fps);
+            let mut last_frame: Option<Instant> = None;
 
             loop {
-                ui.render(&mut target);
-                fb.draw(target.get_data());
+                // render when something changed, at most once a frame
+                let next_frame = last_frame.map_or(Instant::now(), |last| last + frame);
+                if ui.needs_render() && next_frame <= Instant::now() {
+                    ui.render(&mut target);
+                    fb.draw(target.get_data());
+                    last_frame = Some(Instant::now());
+                    continue;
+                }
+
+                // wait for an event, the current screen's tick or a pending frame
+                let pending = if ui.needs_render() { Some(next_frame) } else { None };
+                let wake = match (pending, ui.next_tick()) {
+                    (Some(frame), Some(tick)) => Some(frame.min(tick)),
+                    (frame, tick) => frame.or(tick),
+                };
 
-                let input = rx.recv().unwrap();
-                ui.handle(input);
+                let event = match wake {
+                    Some(at) => match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                        Ok(event) => Some(event),
+                        Err(mpsc::RecvTimeoutError::Timeout) => None,
+                        Err(err) => panic!("{}", err),
+                    },
+                    None => Some(rx.recv().unwrap()),
+                };
+
+                if let Some(event) = event {
+                    ui.event(event);
+                }
+                ui.tick();
             }
         },
         Err(_) => {
@@ -43,7 +88,7 @@ fn ui_loop(rx: mpsc::Receiver<ui::Input>) {
     }
 }
 
-fn enc_loop(tx: mpsc::Sender<ui::Input>) {
+fn enc_loop(tx: mpsc::Sender<ui::Event>) {
     let conf = &config::CONFIG.get();
 
     match InputDevice::open(&conf.devices.encoder) {
@@ -51,9 +96,9 @@ fn enc_loop(tx: mpsc::Sender<ui::Input>) {
             loop {
                 let event = device.read_event().unwrap();
                 if event.value == 1 {
-                    tx.send(ui::Input::Right).unwrap();
+                    tx.send(ui::Event::Input(ui::Input::Right)).unwrap();
                 } else if event.value == -1 {
-                    tx.send(ui::Input::Left).unwrap();
+                    tx.send(ui::Event::Input(ui::Input::Left)).unwrap();
                 }
             }
         },
@@ -64,16 +109,37 @@ fn enc_loop(tx: mpsc::Sender<ui::Input>) {
     }
 }
 
-fn button_loop(tx: mpsc::Sender<ui::Input>) {
+fn button_loop(tx: mpsc::Sender<ui::Event>) {
     let conf = &config::CONFIG.get();
 
     match InputDevice::open(&conf.devices.button) {
         Ok(mut device) => {
+            // bumped on every press and release, so a hold timer can tell
+            // whether the press it was started for is still going
+            let presses = Arc::new(AtomicUsize::new(0));
+
             loop {
                 let event = device.read_event().unwrap();
+                if event.type_ != EV_KEY {
+                    continue;
+                }
 
                 if event.value == 1 {
-                    tx.send(ui::Input::Press).unwrap();
+                    let press = presses.fetch_add(1, Ordering::SeqCst) + 1;
+                    tx.send(ui::Event::Input(ui::Input::Down)).unwrap();
+                    tx.send(ui::Event::Input(ui::Input::Press)).unwrap();
+
+                    let presses = presses.clone();
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        thread::sleep(LONG_PRESS);
+                        if presses.load(Ordering::SeqCst) == press {
+                            tx.send(ui::Event::Input(ui::Input::LongPress)).unwrap();
+                        }
+                    });
+                } else if event.value == 0 {
+                    presses.fetch_add(1, Ordering::SeqCst);
+                    tx.send(ui::Event::Input(ui::Input::Release)).unwrap();
                 }
             }
         },
@@ -84,7 +150,37 @@ fn button_loop(tx: mpsc::Sender<ui::Input>) {
     }
 }
 
+// block the signals that end scthing in every thread so that `signal_loop`
+// gets them instead and can clean up first
+fn block_signals() -> libc::sigset_t {
+    unsafe {
+        let mut signals: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut signals);
+        libc::sigaddset(&mut signals, libc::SIGINT);
+        libc::sigaddset(&mut signals, libc::SIGTERM);
+        libc::sigaddset(&mut signals, libc::SIGHUP);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &signals, ptr::null_mut());
+        signals
+    }
+}
+
+fn signal_loop(signals: libc::sigset_t) {
+    let mut signal = 0;
+    unsafe {
+        libc::sigwait(&signals, &mut signal);
+    }
+
+    server::stop();
+    framebuffer::restore();
+    process::exit(128 + signal);
+}
+
 fn main() {
+    let signals = block_signals();
+    thread::spawn(move || {
+        signal_loop(signals);
+    });
+
     let matches = App::new("")
         .version("0.1.0")
         .arg(Arg::with_name("config")
@@ -99,7 +195,9 @@ fn main() {
     let conf_path = matches.value_of("config").unwrap();
     let conf = config::parse(conf_path).unwrap();
 
+    osc::start(conf.osc.queue_size, conf.osc.max_rate);
     config::CONFIG.set(conf);
+    ui::check_labels();
 
     let (tx, rx) = mpsc::channel();
 
@@ -117,7 +215,46 @@ fn main() {
         button_loop(button_tx);
     });
 
+    let conf = &*config::CONFIG.get();
+    let remote_thread = conf.remote.as_ref().map(|remote| {
+        let addr = remote.addr.clone();
+        let remote_tx = tx.clone();
+        thread::spawn(move || {
+            remote::listen(&addr, remote_tx);
+        })
+    });
+
+    let status_thread = conf.server.as_ref().map(|server| {
+        status::monitor(server, tx.clone())
+    });
+
+    let server_thread = conf.server.as_ref().and_then(|server| {
+        server::start(server, tx.clone())
+    });
+
+    let synthdefs_thread = conf.server.as_ref().and_then(|server| {
+        synthdefs::start(server, tx.clone())
+    });
+
     ui_thread.join().unwrap();
     enc_thread.join().unwrap();
     button_thread.join().unwrap();
+
+    if let Some(remote_thread) = remote_thread {
+        remote_thread.join().unwrap();
+    }
+
+    if let Some(status_thread) = status_thread {
+        status_thread.join().unwrap();
+    }
+
+    if let Some(server_thread) = server_thread {
+        server_thread.join().unwrap();
+    }
+
+    if let Some(synthdefs_thread) = synthdefs_thread {
+        synthdefs_thread.join().unwrap();
+    }
+
+    server::stop();
 }
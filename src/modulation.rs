@@ -0,0 +1,154 @@
+use crate::param;
+use rand::Rng;
+use serde_derive::Deserialize;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Shape {
+    Sine,
+    Triangle,
+    Square,
+    SampleHold,
+    RandomWalk,
+}
+
+#[derive(Debug)]
+pub struct Lfo {
+    shape: Shape,
+    rate: f32,
+    depth: f32,
+    phase: f32,
+    held: f32,
+}
+
+impl Lfo {
+    // rate is in Hz, depth is a fraction of the param range
+    pub fn new(shape: Shape, rate: f32, depth: f32) -> Self {
+        Lfo {
+            shape: shape,
+            rate: rate,
+            depth: depth,
+            phase: 0.0,
+            held: 0.0,
+        }
+    }
+
+    // advance by `dt` seconds and return the output in -1..1
+    fn next(&mut self, dt: f32) -> f32 {
+        let phase = self.phase + self.rate * dt;
+        let wrapped = phase >= 1.0;
+        self.phase = phase.fract();
+
+        match self.shape {
+            Shape::Sine => (self.phase * 2.0 * PI).sin(),
+            Shape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Shape::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+            Shape::SampleHold => {
+                if wrapped {
+                    self.held = rand::thread_rng().gen_range(-1.0, 1.0);
+                }
+                self.held
+            },
+            Shape::RandomWalk => {
+                let step = rand::thread_rng().gen_range(-1.0, 1.0) * self.rate * dt * 4.0;
+                self.held = (self.held + step).max(-1.0).min(1.0);
+                self.held
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Modulator {
+    param: param::Shared,
+    lfo: Lfo,
+}
+
+impl Modulator {
+    pub fn new(param: param::Shared, lfo: Lfo) -> Self {
+        Modulator {
+            param: param,
+            lfo: lfo,
+        }
+    }
+
+    fn tick(&mut self, dt: f32) {
+        let mut param = self.param.lock().unwrap();
+        let range = param.max - param.min;
+        let offset = self.lfo.next(dt) * self.lfo.depth * range;
+        let value = param.clamp(param.value + offset);
+        param.modulated = Some(value);
+        param.send();
+    }
+
+    fn reset(&mut self) {
+        self.param.lock().unwrap().modulated = None;
+    }
+}
+
+#[derive(Debug)]
+pub struct Modulation {
+    modulators: Vec<Modulator>,
+    rate: f32,
+    running: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<Vec<Modulator>>>,
+}
+
+impl Modulation {
+    // rate is the control rate in Hz
+    pub fn new(modulators: Vec<Modulator>, rate: f32) -> Self {
+        Modulation {
+            modulators: modulators,
+            rate: rate,
+            running: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.thread.is_some() || self.modulators.is_empty() {
+            return;
+        }
+
+        let mut modulators = self.modulators.drain(..).collect::<Vec<Modulator>>();
+        let interval = Duration::from_secs_f32(1.0 / self.rate);
+        let running = self.running.clone();
+
+        running.store(true, Ordering::SeqCst);
+
+        self.thread = Some(thread::spawn(move || {
+            let mut last = Instant::now();
+
+            while running.load(Ordering::SeqCst) {
+                let now = Instant::now();
+                let dt = now.duration_since(last).as_secs_f32();
+                last = now;
+
+                for modulator in modulators.iter_mut() {
+                    modulator.tick(dt);
+                }
+
+                thread::sleep(interval);
+            }
+
+            modulators
+        }));
+    }
+
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Some(thread) = self.thread.take() {
+            self.modulators = thread.join().unwrap();
+
+            for modulator in self.modulators.iter_mut() {
+                modulator.reset();
+            }
+        }
+    }
+}
@@ -1,12 +1,86 @@
-use crate::config;
+mod client;
+mod connection;
+
+pub use self::connection::Connection;
+
 use std::io;
-use rand::Rng;
-use rosc::{OscPacket,OscMessage,OscType};
-use rosc::encoder;
-use std::net::{UdpSocket};
+use rosc::{OscBundle,OscPacket,OscMessage,OscType};
+use serde_derive::Deserialize;
+use state;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub type Type = OscType;
 
+static SENDER: state::Storage<Arc<client::Queue>> = state::Storage::new();
+
+// seconds from the NTP epoch (1900) to the unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Udp,
+    Tcp,
+    Unix,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport::Udp
+    }
+}
+
+// how packets are delimited on stream transports: OSC 1.0 uses a 32-bit
+// size prefix (what scsynth expects with -t), OSC 1.1 uses SLIP
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Framing {
+    Length,
+    Slip,
+}
+
+impl Default for Framing {
+    fn default() -> Self {
+        Framing::Length
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub transport: Transport,
+    pub framing: Framing,
+    pub addr: String,
+}
+
+impl Target {
+    // `addr` may start with udp://, tcp:// or unix:// to override the default transport
+    pub fn parse(addr: &str, transport: Transport, framing: Framing) -> Result<Target, String> {
+        let (transport, addr) = match addr.find("://") {
+            Some(i) => {
+                let transport = match &addr[..i] {
+                    "udp" => Transport::Udp,
+                    "tcp" => Transport::Tcp,
+                    "unix" => Transport::Unix,
+                    scheme => return Err(format!("unknown OSC transport {}", scheme)),
+                };
+                (transport, &addr[i + 3..])
+            },
+            None => (transport, addr),
+        };
+
+        if addr.is_empty() {
+            return Err("empty OSC address".to_string());
+        }
+
+        Ok(Target {
+            transport: transport,
+            framing: framing,
+            addr: addr.to_string(),
+        })
+    }
+}
+
 #[derive(Debug, Fail)]
 pub enum Error {
     #[fail(display = "OSC error")]
@@ -17,6 +91,8 @@ pub enum Error {
     SocketError {
         error: io::Error,
     },
+    #[fail(display = "OSC sender stopped")]
+    SenderStopped,
 }
 
 impl From<rosc::OscError> for Error {
@@ -31,39 +107,95 @@ impl From<io::Error> for Error {
     }
 }
 
-fn check_port(port: u16) -> bool {
-    match UdpSocket::bind(("127.0.0.1", port)) {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+fn enqueue(outgoing: client::Outgoing) -> Result<(), Error> {
+    SENDER.get().push(outgoing)
 }
 
-fn random_port() -> u16 {
-    let mut rng = rand::thread_rng();
+// start the sender thread, must be called before sending anything
+pub fn start(queue_size: usize, rate: f32) {
+    SENDER.set(client::spawn(queue_size, rate));
+}
 
-    loop {
-        let port = rng.gen_range(1024, 65535);
+pub fn send(targets: &[Target], name: &str, args: Option<Vec<OscType>>) -> Result<(), Error> {
+    let mut bundle = Bundle::new();
+    bundle.add(targets, name, args);
+    bundle.send(None)
+}
 
-        if check_port(port) {
-            return port;
-        }
+// an NTP timetag `latency` from now, or "immediately" without latency
+pub fn timetag(latency: Option<Duration>) -> OscType {
+    match latency {
+        Some(latency) => {
+            let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + latency;
+            let secs = time.as_secs() + NTP_UNIX_OFFSET;
+            let frac = ((time.subsec_nanos() as u64) << 32) / 1_000_000_000;
+            OscType::Time(secs as u32, frac as u32)
+        },
+        None => OscType::Time(0, 1),
     }
 }
 
-pub fn send(name: &str, args: Option<Vec<OscType>>) -> Result<(), Error> {
-    let packet = OscPacket::Message(
-        OscMessage {
+// collects related messages so they're sent as one bundle per target
+#[derive(Debug)]
+pub struct Bundle {
+    messages: Vec<(Vec<Target>, OscMessage)>,
+    key: Option<String>,
+}
+
+impl Bundle {
+    pub fn new() -> Self {
+        Bundle {
+            messages: vec![],
+            key: None,
+        }
+    }
+
+    // a newer bundle with the same key replaces this one if it's still
+    // waiting to be sent
+    pub fn coalesce(&mut self, key: &str) {
+        self.key = Some(key.to_string());
+    }
+
+    pub fn add(&mut self, targets: &[Target], name: &str, args: Option<Vec<OscType>>) {
+        let message = OscMessage {
             addr: name.to_string(),
             args: args,
+        };
+
+        self.messages.push((targets.to_vec(), message));
+    }
+
+    pub fn send(self, latency: Option<Duration>) -> Result<(), Error> {
+        let mut packets: Vec<(Target, Vec<OscPacket>)> = vec![];
+
+        for (targets, message) in self.messages {
+            for target in targets {
+                let packet = OscPacket::Message(message.clone());
+
+                match packets.iter_mut().find(|(t, _)| *t == target) {
+                    Some((_, content)) => content.push(packet),
+                    None => packets.push((target, vec![packet])),
+                }
+            }
         }
-    );
 
-    let bytes = encoder::encode(&packet)?;
-    let port = random_port();
-    let socket = UdpSocket::bind(("127.0.0.1", port))?;
+        let packets = packets.into_iter().map(|(target, mut content)| {
+            // a lone message doesn't need a bundle unless it's scheduled
+            let packet = if content.len() == 1 && latency.is_none() {
+                content.remove(0)
+            } else {
+                OscPacket::Bundle(OscBundle {
+                    timetag: timetag(latency),
+                    content: content,
+                })
+            };
 
-    let conf = &*config::CONFIG.get();
-    socket.send_to(&bytes, &conf.osc.addr)?;
+            (target, packet)
+        }).collect();
 
-    Ok(())
+        enqueue(client::Outgoing {
+            packets: packets,
+            key: self.key,
+        })
+    }
 }
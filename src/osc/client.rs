@@ -0,0 +1,329 @@
+use crate::osc::{Error, Framing, Target, Transport};
+use rosc::encoder;
+use rosc::OscPacket;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const STATS_INTERVAL: Duration = Duration::from_secs(30);
+
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+pub static SENT: AtomicUsize = AtomicUsize::new(0);
+pub static COALESCED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub struct Outgoing {
+    pub packets: Vec<(Target, OscPacket)>,
+    // queued packets with the same key are replaced rather than sent twice
+    pub key: Option<String>,
+}
+
+pub fn frame(bytes: &[u8], framing: Framing) -> Vec<u8> {
+    match framing {
+        Framing::Length => {
+            let mut framed = (bytes.len() as u32).to_be_bytes().to_vec();
+            framed.extend_from_slice(bytes);
+            framed
+        },
+        Framing::Slip => {
+            let mut framed = vec![SLIP_END];
+            for byte in bytes {
+                match *byte {
+                    SLIP_END => framed.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+                    SLIP_ESC => framed.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+                    byte => framed.push(byte),
+                }
+            }
+            framed.push(SLIP_END);
+            framed
+        },
+    }
+}
+
+// read one packet off a stream, the reverse of `frame`
+pub fn read_frame<R: Read>(stream: &mut R, framing: Framing) -> Result<Vec<u8>, io::Error> {
+    match framing {
+        Framing::Length => {
+            let mut size = [0; 4];
+            stream.read_exact(&mut size)?;
+            let mut bytes = vec![0; u32::from_be_bytes(size) as usize];
+            stream.read_exact(&mut bytes)?;
+            Ok(bytes)
+        },
+        Framing::Slip => {
+            let mut bytes = vec![];
+            let mut byte = [0; 1];
+            let mut escaped = false;
+
+            loop {
+                stream.read_exact(&mut byte)?;
+                match (byte[0], escaped) {
+                    // packets can start with an END too
+                    (SLIP_END, false) if bytes.is_empty() => {},
+                    (SLIP_END, false) => return Ok(bytes),
+                    (SLIP_ESC, false) => {
+                        escaped = true;
+                        continue;
+                    },
+                    (SLIP_ESC_END, true) => bytes.push(SLIP_END),
+                    (SLIP_ESC_ESC, true) => bytes.push(SLIP_ESC),
+                    (byte, _) => bytes.push(byte),
+                }
+
+                escaped = false;
+            }
+        },
+    }
+}
+
+// owns the sockets for every target, only used from the sender thread
+struct Client {
+    udp: UdpSocket,
+    unix: UnixDatagram,
+    streams: HashMap<String, TcpStream>,
+}
+
+impl Client {
+    fn new() -> Result<Client, io::Error> {
+        Ok(Client {
+            udp: UdpSocket::bind("0.0.0.0:0")?,
+            unix: UnixDatagram::unbound()?,
+            streams: HashMap::new(),
+        })
+    }
+
+    fn send(&mut self, target: &Target, packet: &OscPacket) -> Result<(), Error> {
+        let bytes = encoder::encode(packet)?;
+
+        match target.transport {
+            Transport::Udp => { self.udp.send_to(&bytes, &target.addr)?; },
+            Transport::Unix => { self.unix.send_to(&bytes, &target.addr)?; },
+            Transport::Tcp => self.send_tcp(&frame(&bytes, target.framing), &target.addr)?,
+        }
+
+        Ok(())
+    }
+
+    fn send_tcp(&mut self, framed: &[u8], addr: &str) -> Result<(), Error> {
+        // reuse the open connection, reconnecting if it was dropped. A write
+        // to a peer that has closed its end still succeeds locally, so the
+        // stream is checked first rather than losing the packet
+        if let Some(stream) = self.streams.get_mut(addr) {
+            if is_open(stream) && stream.write_all(framed).is_ok() {
+                return Ok(());
+            }
+            self.streams.remove(addr);
+        }
+
+        let socket_addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        })?;
+
+        let mut stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.write_all(framed)?;
+        self.streams.insert(addr.to_string(), stream);
+
+        Ok(())
+    }
+}
+
+// peeks without blocking, a closed stream reads as EOF or an error while an
+// open one has nothing to read or a reply waiting
+fn is_open(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+
+    let mut buf = [0; 1];
+    let open = match stream.peek(&mut buf) {
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(err) => err.kind() == io::ErrorKind::WouldBlock,
+    };
+
+    stream.set_nonblocking(false).is_ok() && open
+}
+
+// the packets waiting to be sent, shared by the senders and the sender thread
+#[derive(Debug)]
+pub struct Queue {
+    size: usize,
+    packets: Mutex<VecDeque<Outgoing>>,
+    // signalled whenever a packet is queued or taken off
+    changed: Condvar,
+    running: AtomicBool,
+}
+
+impl Queue {
+    fn new(size: usize) -> Self {
+        Queue {
+            size: size,
+            packets: Mutex::new(VecDeque::new()),
+            changed: Condvar::new(),
+            running: AtomicBool::new(true),
+        }
+    }
+
+    // keyed packets are only state updates, so they replace a queued packet
+    // with the same key. There's at most one queued per key so they don't
+    // count towards the size and the newest value is never lost. Anything
+    // else waits for room.
+    pub fn push(&self, outgoing: Outgoing) -> Result<(), Error> {
+        let mut packets = self.packets.lock().unwrap();
+
+        if outgoing.key.is_some() {
+            if let Some(queued) = packets.iter_mut().find(|o| o.key == outgoing.key) {
+                *queued = outgoing;
+                COALESCED.fetch_add(1, Ordering::SeqCst);
+                return Ok(());
+            }
+        } else {
+            while Queue::unkeyed(&packets) >= self.size && self.running.load(Ordering::SeqCst) {
+                packets = self.changed.wait(packets).unwrap();
+            }
+        }
+
+        if !self.running.load(Ordering::SeqCst) {
+            return Err(Error::SenderStopped);
+        }
+
+        packets.push_back(outgoing);
+        self.changed.notify_all();
+        Ok(())
+    }
+
+    fn unkeyed(packets: &VecDeque<Outgoing>) -> usize {
+        packets.iter().filter(|o| o.key.is_none()).count()
+    }
+
+    fn pop(&self) -> Outgoing {
+        let mut packets = self.packets.lock().unwrap();
+
+        loop {
+            if let Some(outgoing) = packets.pop_front() {
+                self.changed.notify_all();
+                return outgoing;
+            }
+
+            packets = self.changed.wait(packets).unwrap();
+        }
+    }
+
+    fn stop(&self) {
+        let _packets = self.packets.lock().unwrap();
+        self.running.store(false, Ordering::SeqCst);
+        self.changed.notify_all();
+    }
+}
+
+fn log_stats() {
+    println!(
+        "OSC: {} sent, {} coalesced",
+        SENT.load(Ordering::SeqCst),
+        COALESCED.load(Ordering::SeqCst),
+    );
+}
+
+fn run(queue: &Queue, rate: f32) {
+    let mut client = match Client::new() {
+        Ok(client) => client,
+        Err(err) => {
+            println!("error opening OSC sockets: {}", err);
+            queue.stop();
+            return;
+        },
+    };
+
+    let interval = Duration::from_secs_f32(1.0 / rate);
+    let mut last_sent: Option<Instant> = None;
+    let mut last_stats = Instant::now();
+    let mut logged = 0;
+
+    loop {
+        // stay under the max rate, packets keep being coalesced in the
+        // queue while waiting
+        if let Some(wait) = last_sent.and_then(|t| interval.checked_sub(t.elapsed())) {
+            thread::sleep(wait);
+        }
+
+        let outgoing = queue.pop();
+        for (target, packet) in outgoing.packets.iter() {
+            if let Err(err) = client.send(target, packet) {
+                println!("error sending OSC message to {}: {}", target.addr, err);
+            }
+        }
+
+        SENT.fetch_add(1, Ordering::SeqCst);
+        last_sent = Some(Instant::now());
+
+        let sent = SENT.load(Ordering::SeqCst);
+        if last_stats.elapsed() >= STATS_INTERVAL && sent != logged {
+            log_stats();
+            logged = sent;
+            last_stats = Instant::now();
+        }
+    }
+}
+
+// `rate` is the max number of packets sent per second
+pub fn spawn(queue_size: usize, rate: f32) -> Arc<Queue> {
+    let queue = Arc::new(Queue::new(queue_size));
+    let sender_queue = queue.clone();
+
+    thread::spawn(move || {
+        run(&sender_queue, rate);
+    });
+
+    queue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const PACKETS: [&[u8]; 2] = [
+        &[0x2F, 0x73, 0x00, 0x00],
+        &[SLIP_END, 0x01, SLIP_ESC, SLIP_ESC_END, SLIP_ESC_ESC, SLIP_END],
+    ];
+
+    fn round_trip(framing: Framing) {
+        let framed = PACKETS.iter().flat_map(|bytes| frame(bytes, framing)).collect::<Vec<u8>>();
+        let mut stream = Cursor::new(framed);
+
+        for bytes in PACKETS.iter() {
+            assert_eq!(read_frame(&mut stream, framing).unwrap(), bytes.to_vec());
+        }
+
+        assert!(read_frame(&mut stream, framing).is_err());
+    }
+
+    #[test]
+    fn slip_escapes_end_and_esc() {
+        let framed = frame(&[1, SLIP_END, 2, SLIP_ESC, 3], Framing::Slip);
+        assert_eq!(framed, vec![SLIP_END, 1, SLIP_ESC, SLIP_ESC_END, 2, SLIP_ESC, SLIP_ESC_ESC, 3, SLIP_END]);
+    }
+
+    #[test]
+    fn slip_round_trip() {
+        round_trip(Framing::Slip);
+    }
+
+    #[test]
+    fn length_round_trip() {
+        assert_eq!(frame(&[1, 2, 3], Framing::Length), vec![0, 0, 0, 3, 1, 2, 3]);
+        round_trip(Framing::Length);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::osc::client::{frame, read_frame};
+use crate::osc::{Framing, Target, Transport};
+use rosc::decoder;
+use std::io;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// a connection for commands that need a reply, like /status or /d_recv,
+// over the transport and framing set in [osc]
+pub enum Connection {
+    Udp(UdpSocket, String),
+    Tcp(TcpStream, Framing),
+}
+
+impl Connection {
+    pub fn open(target: &Target) -> Result<Connection, io::Error> {
+        match target.transport {
+            Transport::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                Ok(Connection::Udp(socket, target.addr.clone()))
+            },
+            Transport::Tcp => {
+                let addr = target.addr.to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+                })?;
+                let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+                stream.set_nodelay(true)?;
+                Ok(Connection::Tcp(stream, target.framing))
+            },
+            Transport::Unix => Err(io::Error::new(io::ErrorKind::InvalidInput, "unix sockets can't get replies")),
+        }
+    }
+
+    pub fn send(&mut self, bytes: &[u8]) -> Result<(), io::Error> {
+        match self {
+            Connection::Udp(socket, addr) => socket.send_to(bytes, addr.as_str()).map(|_| ()),
+            Connection::Tcp(stream, framing) => stream.write_all(&frame(bytes, *framing)),
+        }
+    }
+
+    // None when nothing arrives in time, an error means a stream should be
+    // reopened
+    pub fn recv(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, io::Error> {
+        let received = match self {
+            Connection::Udp(socket, _) => {
+                socket.set_read_timeout(Some(timeout))?;
+                let mut buf = [0u8; decoder::MTU];
+                socket.recv_from(&mut buf).map(|(size, _)| buf[..size].to_vec())
+            },
+            Connection::Tcp(stream, framing) => {
+                stream.set_read_timeout(Some(timeout))?;
+                read_frame(stream, *framing)
+            },
+        };
+
+        match received {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config;
+use crate::osc;
+use crate::template::{self, Template};
+use std::sync::{Arc, Mutex};
+
+pub type Shared = Arc<Mutex<Param>>;
+
+// discrete kinds keep their value as a whole number in `value` (0/1 for
+// toggles, the index for choices) and only differ in how it's sent and shown
+#[derive(Debug, Clone)]
+pub enum Kind {
+    Float,
+    Int,
+    Toggle,
+    Choice(Vec<config::Choice>),
+}
+
+impl Default for Kind {
+    fn default() -> Self {
+        Kind::Float
+    }
+}
+
+impl Kind {
+    pub fn is_discrete(&self) -> bool {
+        match self {
+            Kind::Float => false,
+            _ => true,
+        }
+    }
+
+    pub fn arg(&self, value: f32) -> osc::Type {
+        match self {
+            Kind::Float => osc::Type::Float(value),
+            Kind::Int => osc::Type::Int(value.round() as i32),
+            Kind::Toggle => osc::Type::Bool(value >= 0.5),
+            Kind::Choice(choices) => match &choices[value.round() as usize] {
+                config::Choice::Symbol(symbol) => osc::Type::String(symbol.clone()),
+                config::Choice::Value { value, .. } => osc::Type::Float(*value),
+            },
+        }
+    }
+
+    pub fn display(&self, value: f32) -> String {
+        match self {
+            Kind::Float => format!("{:.*}", 2, value),
+            Kind::Int => format!("{}", value.round() as i32),
+            Kind::Toggle => if value >= 0.5 { "on".to_string() } else { "off".to_string() },
+            Kind::Choice(choices) => choices[value.round() as usize].label().to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: String,
+    pub kind: Kind,
+    pub value: f32,
+    pub step: f32,
+    pub min: f32,
+    pub max: f32,
+    pub modulated: Option<f32>,
+    pub unit: Option<String>,
+    messages: template::Shared,
+    template: Option<Template>,
+    targets: Option<Vec<osc::Target>>,
+}
+
+impl Param {
+    pub fn new(
+        name: &str,
+        kind: Kind,
+        value: f32,
+        step: f32,
+        min: f32,
+        max: f32,
+        unit: Option<String>,
+        messages: template::Shared,
+        template: Option<Template>,
+        targets: Option<Vec<osc::Target>>,
+    ) -> Self {
+        Param {
+            name: name.to_string(),
+            kind: kind,
+            value: value,
+            step: step,
+            min: min,
+            max: max,
+            modulated: None,
+            unit: unit,
+            messages: messages,
+            template: template,
+            targets: targets,
+        }
+    }
+
+    pub fn shared(self) -> Shared {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn inc(&mut self) {
+        self.value += self.step;
+        if self.value > self.max {
+            self.value = self.max;
+        }
+    }
+
+    pub fn dec(&mut self) {
+        self.value -= self.step;
+        if self.value < self.min {
+            self.value = self.min;
+        }
+    }
+
+    pub fn clamp(&self, value: f32) -> f32 {
+        let value = value.max(self.min).min(self.max);
+
+        if self.kind.is_discrete() {
+            value.round()
+        } else {
+            value
+        }
+    }
+
+    pub fn perc(&self) -> f32 {
+        self.value / self.max
+    }
+
+    // the value SC should currently have
+    pub fn output(&self) -> f32 {
+        self.modulated.unwrap_or(self.value)
+    }
+
+    pub fn display(&self) -> String {
+        let value = self.kind.display(self.value);
+
+        match (&self.kind, &self.unit) {
+            (Kind::Float, Some(unit)) | (Kind::Int, Some(unit)) => format!("{}{}", value, unit),
+            _ => value,
+        }
+    }
+
+    pub fn send(&self) {
+        self.send_value(self.output());
+    }
+
+    pub fn send_value(&self, value: f32) {
+        let mut bundle = osc::Bundle::new();
+        bundle.coalesce(&format!("{}/{}", self.messages.path(), self.name));
+        self.add_value(&mut bundle, value);
+
+        if let Err(err) = bundle.send(None) {
+            println!("error sending OSC message: {}", err);
+        }
+    }
+
+    pub fn add(&self, bundle: &mut osc::Bundle) {
+        self.add_value(bundle, self.output());
+    }
+
+    pub fn add_value(&self, bundle: &mut osc::Bundle, value: f32) {
+        let targets = self.targets.as_ref();
+        self.messages.set(bundle, self.template.as_ref(), targets, &self.name, self.kind.arg(value));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::automation::Recording;
+use crate::config;
+use crate::sequencer::Pattern;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use toml;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "IO error: {}", error)]
+    IoError {
+        error: io::Error,
+    },
+    #[fail(display = "TOML error: {}", error)]
+    TomlError {
+        error: toml::ser::Error,
+    },
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::IoError { error: error }
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(error: toml::ser::Error) -> Error {
+        Error::TomlError { error: error }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Preset {
+    // param values by name
+    #[serde(default)]
+    pub params: HashMap<String, f32>,
+    pub pattern: Option<Pattern>,
+    #[serde(default)]
+    pub automations: HashMap<String, Recording>,
+}
+
+// presets are kept by the patch's path ("menu/patch") so patches with the
+// same name in different menus don't share one
+fn path(patch: &str) -> Option<PathBuf> {
+    let conf = &*config::CONFIG.get();
+    let presets = conf.presets.as_ref()?;
+    Some(PathBuf::from(&presets.dir).join(format!("{}.toml", patch)))
+}
+
+pub fn load(patch: &str) -> Option<Preset> {
+    let path = path(patch)?;
+    let preset_toml = fs::read_to_string(&path).ok()?;
+
+    match toml::from_str::<Preset>(&preset_toml) {
+        Ok(preset) => {
+            if let Some(pattern) = &preset.pattern {
+                if let Err(err) = pattern.validate() {
+                    println!("error loading preset {}: {}", path.display(), err);
+                    return None;
+                }
+            }
+
+            Some(preset)
+        },
+        Err(err) => {
+            println!("error parsing preset {}: {}", path.display(), err);
+            None
+        },
+    }
+}
+
+pub fn save(patch: &str, preset: &Preset) -> Result<(), Error> {
+    if let Some(path) = path(patch) {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, toml::to_string(preset)?)?;
+    }
+
+    Ok(())
+}
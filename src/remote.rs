@@ -0,0 +1,220 @@
+use crate::osc;
+use crate::param;
+use crate::ui::{self, UI};
+use rosc::{decoder, encoder};
+use rosc::{OscMessage, OscPacket, OscType};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc};
+
+const PREFIX: &str = "/scthing";
+
+// a message received from a remote client, handled on the UI thread
+#[derive(Debug)]
+pub struct Request {
+    message: OscMessage,
+    socket: Arc<UdpSocket>,
+    sender: SocketAddr,
+}
+
+impl Request {
+    fn args(&self) -> &[OscType] {
+        self.message.args.as_ref().map_or(&[], |args| &args[..])
+    }
+
+    fn path_arg(&self) -> Result<&str, String> {
+        match self.args().get(0) {
+            Some(OscType::String(path)) => Ok(path),
+            _ => Err("expected a path".to_string()),
+        }
+    }
+
+    fn send(&self, packet: &OscPacket) -> Result<(), osc::Error> {
+        let bytes = encoder::encode(packet)?;
+        self.socket.send_to(&bytes, self.sender)?;
+        Ok(())
+    }
+
+    // replies go back to the port the request came from
+    fn reply(&self, addr: &str, args: Vec<OscType>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args: if args.is_empty() { None } else { Some(args) },
+        });
+
+        if let Err(err) = self.send(&packet) {
+            println!("error replying to {}: {}", self.sender, err);
+        }
+    }
+
+    fn done(&self) {
+        let addr = OscType::String(self.message.addr.clone());
+        self.reply(&format!("{}/done", PREFIX), vec![addr]);
+    }
+
+    fn fail(&self, err: &str) {
+        let addr = OscType::String(self.message.addr.clone());
+        self.reply(&format!("{}/error", PREFIX), vec![addr, OscType::String(err.to_string())]);
+    }
+}
+
+fn messages(packet: OscPacket) -> Vec<OscMessage> {
+    match packet {
+        OscPacket::Message(message) => vec![message],
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(messages).collect(),
+    }
+}
+
+pub fn listen(addr: &str, tx: mpsc::Sender<ui::Event>) {
+    let socket = match UdpSocket::bind(addr) {
+        Ok(socket) => Arc::new(socket),
+        Err(err) => {
+            println!("error listening for OSC on {}: {}", addr, err);
+            return;
+        },
+    };
+
+    let mut buf = [0u8; decoder::MTU];
+
+    loop {
+        let (size, sender) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) => {
+                println!("error receiving OSC message: {}", err);
+                continue;
+            },
+        };
+
+        let packet = match decoder::decode(&buf[..size]) {
+            Ok(packet) => packet,
+            Err(_) => {
+                println!("error decoding OSC packet from {}", sender);
+                continue;
+            },
+        };
+
+        for message in messages(packet) {
+            let request = Request {
+                message: message,
+                socket: socket.clone(),
+                sender: sender,
+            };
+
+            tx.send(ui::Event::Remote(request)).unwrap();
+        }
+    }
+}
+
+fn value(param: &param::Param, arg: &OscType) -> Result<f32, String> {
+    match (arg, &param.kind) {
+        (OscType::Float(value), _) => Ok(*value),
+        (OscType::Double(value), _) => Ok(*value as f32),
+        (OscType::Int(value), _) => Ok(*value as f32),
+        (OscType::Bool(value), _) => Ok(if *value { 1.0 } else { 0.0 }),
+        (OscType::String(label), param::Kind::Choice(choices)) => {
+            choices.iter().position(|choice| choice.label() == label)
+                .map(|index| index as f32)
+                .ok_or_else(|| format!("unknown choice {}", label))
+        },
+        _ => Err("expected a number".to_string()),
+    }
+}
+
+// with a value sets the param, either way replies with its current value
+fn param(ui: &mut UI, request: &Request, path: &str) -> Result<(), String> {
+    let param = ui.param(path).ok_or_else(|| format!("unknown param {}", path))?;
+    let mut param = param.lock().unwrap();
+
+    if let Some(arg) = request.args().get(0) {
+        let value = value(&param, arg)?;
+        param.value = param.clamp(value);
+        param.send();
+    }
+
+    let args = vec![OscType::Float(param.value), OscType::String(param.display())];
+    request.reply(&request.message.addr, args);
+
+    Ok(())
+}
+
+// a press from a remote is a whole click in the order the button sends it,
+// a long press being a press that's held before the release
+fn press(ui: &mut UI, request: &Request, input: ui::Input) -> Result<(), String> {
+    let long = match input {
+        ui::Input::LongPress => true,
+        _ => false,
+    };
+
+    ui.handle(ui::Input::Down);
+    ui.handle(ui::Input::Press);
+    if long {
+        ui.handle(ui::Input::LongPress);
+    }
+    ui.handle(ui::Input::Release);
+    request.done();
+    Ok(())
+}
+
+fn navigate(ui: &mut UI, request: &Request, input: ui::Input) -> Result<(), String> {
+    ui.handle(input);
+    request.done();
+    Ok(())
+}
+
+fn start(ui: &mut UI, request: &Request) -> Result<(), String> {
+    let path = request.path_arg()?;
+    if !ui.is_patch(path) {
+        return Err(format!("unknown patch {}", path));
+    }
+
+    ui.open(path)?;
+    request.done();
+    Ok(())
+}
+
+fn stop(ui: &mut UI, request: &Request) -> Result<(), String> {
+    let path = request.path_arg()?;
+    if !ui.is_patch(path) {
+        return Err(format!("unknown patch {}", path));
+    }
+
+    ui.close(path)?;
+    request.done();
+    Ok(())
+}
+
+fn load_preset(ui: &mut UI, request: &Request) -> Result<(), String> {
+    ui.load_preset(request.path_arg()?)?;
+    request.done();
+    Ok(())
+}
+
+fn tree(ui: &mut UI, request: &Request) -> Result<(), String> {
+    let paths = ui.paths().into_iter().map(OscType::String).collect();
+    request.reply(&format!("{}/tree", PREFIX), paths);
+    Ok(())
+}
+
+pub fn handle(ui: &mut UI, request: Request) {
+    let addr = request.message.addr.clone();
+
+    let result = if !addr.starts_with(PREFIX) {
+        Err(format!("unknown address {}", addr))
+    } else {
+        match &addr[PREFIX.len()..] {
+            "/ui/left" => navigate(ui, &request, ui::Input::Left),
+            "/ui/right" => navigate(ui, &request, ui::Input::Right),
+            "/ui/press" => press(ui, &request, ui::Input::Press),
+            "/ui/long_press" => press(ui, &request, ui::Input::LongPress),
+            "/patch/start" => start(ui, &request),
+            "/patch/stop" => stop(ui, &request),
+            "/preset/load" => load_preset(ui, &request),
+            "/tree" => tree(ui, &request),
+            path if path.starts_with("/param/") => param(ui, &request, &path["/param/".len()..]),
+            _ => Err(format!("unknown address {}", addr)),
+        }
+    };
+
+    if let Err(err) = result {
+        request.fail(&err);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::config;
+use crate::osc;
+use crate::param;
+use crate::template;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// steps are 16th notes
+pub const STEPS_PER_BEAT: f32 = 4.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TrackKind {
+    Param,
+    Note,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Track {
+    pub name: String,
+    pub kind: TrackKind,
+    pub values: Vec<f32>,
+    pub gates: Vec<bool>,
+    pub step: f32,
+    pub min: f32,
+    pub max: f32,
+    #[serde(skip)]
+    pub param: Option<param::Shared>,
+}
+
+impl Track {
+    pub fn new(name: &str, kind: TrackKind, steps: usize, value: f32, step: f32, min: f32, max: f32) -> Self {
+        Track {
+            name: name.to_string(),
+            kind: kind,
+            values: vec![value; steps],
+            gates: vec![false; steps],
+            step: step,
+            min: min,
+            max: max,
+            param: None,
+        }
+    }
+
+    pub fn display(&self, index: usize) -> String {
+        match &self.param {
+            Some(param) => param.lock().unwrap().kind.display(self.values[index]),
+            None => format!("{}", self.values[index] as i32),
+        }
+    }
+
+    pub fn inc(&mut self, index: usize) {
+        if !self.gates[index] {
+            self.gates[index] = true;
+            return;
+        }
+
+        self.values[index] += self.step;
+        if self.values[index] > self.max {
+            self.values[index] = self.max;
+        }
+    }
+
+    pub fn dec(&mut self, index: usize) {
+        if !self.gates[index] {
+            return;
+        }
+
+        if self.values[index] <= self.min {
+            self.gates[index] = false;
+            return;
+        }
+
+        self.values[index] -= self.step;
+        if self.values[index] < self.min {
+            self.values[index] = self.min;
+        }
+    }
+
+    fn add(&self, bundle: &mut osc::Bundle, messages: &template::Messages, index: usize) {
+        if !self.gates[index] {
+            return;
+        }
+
+        match (&self.kind, &self.param) {
+            (TrackKind::Param, Some(param)) => param.lock().unwrap().add_value(bundle, self.values[index]),
+            (TrackKind::Note, _) => messages.note(bundle, self.values[index]),
+            _ => {},
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pattern {
+    pub bpm: f32,
+    pub steps: usize,
+    pub tracks: Vec<Track>,
+    #[serde(skip)]
+    pub position: Option<usize>,
+}
+
+impl Pattern {
+    pub fn new(bpm: f32, steps: usize, tracks: Vec<Track>) -> Self {
+        Pattern {
+            bpm: bpm,
+            steps: steps,
+            tracks: tracks,
+            position: None,
+        }
+    }
+
+    // saved patterns can be edited by hand, so they're checked before use
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.bpm.is_finite() || self.bpm <= 0.0 {
+            return Err(format!("bpm {} must be more than 0", self.bpm));
+        }
+
+        if self.steps == 0 {
+            return Err("steps must be more than 0".to_string());
+        }
+
+        Ok(())
+    }
+
+    // copy saved steps onto the configured tracks so that adding or
+    // removing tracks in the config doesn't invalidate saved patterns
+    pub fn merge(&mut self, saved: &Pattern) {
+        if saved.validate().is_ok() {
+            self.bpm = saved.bpm;
+        }
+
+        for track in self.tracks.iter_mut() {
+            let saved_track = saved.tracks.iter()
+                .find(|t| t.name == track.name && t.kind == track.kind);
+
+            if let Some(saved_track) = saved_track {
+                let steps = track.values.len()
+                    .min(saved_track.values.len())
+                    .min(saved_track.gates.len());
+
+                for i in 0..steps {
+                    track.values[i] = saved_track.values[i];
+                    track.gates[i] = saved_track.gates[i];
+                }
+            }
+        }
+    }
+
+    fn tick_duration(&self) -> Duration {
+        Duration::from_secs_f32(60.0 / self.bpm / STEPS_PER_BEAT)
+    }
+}
+
+#[derive(Debug)]
+pub struct Sequencer {
+    messages: template::Shared,
+    pattern: Arc<Mutex<Pattern>>,
+    // the running thread's flag, it's left to finish its last step on its
+    // own so stopping doesn't hold up the UI
+    running: Option<Arc<AtomicBool>>,
+}
+
+impl Sequencer {
+    pub fn new(messages: template::Shared, pattern: Pattern) -> Self {
+        Sequencer {
+            messages: messages,
+            pattern: Arc::new(Mutex::new(pattern)),
+            running: None,
+        }
+    }
+
+    pub fn pattern(&self) -> Arc<Mutex<Pattern>> {
+        self.pattern.clone()
+    }
+
+    pub fn start(&mut self) {
+        if self.running.is_some() {
+            return;
+        }
+
+        let conf = &*config::CONFIG.get();
+        let latency = conf.osc.latency();
+        let messages = self.messages.clone();
+        let pattern = self.pattern.clone();
+        let running = Arc::new(AtomicBool::new(true));
+
+        self.running = Some(running.clone());
+
+        thread::spawn(move || {
+            let mut index = 0;
+
+            while running.load(Ordering::SeqCst) {
+                let mut bundle = osc::Bundle::new();
+
+                let duration = {
+                    let mut pattern = pattern.lock().unwrap();
+                    pattern.position = Some(index);
+
+                    for track in pattern.tracks.iter() {
+                        track.add(&mut bundle, &messages, index);
+                    }
+
+                    index = (index + 1) % pattern.steps.max(1);
+                    pattern.tick_duration()
+                };
+
+                if let Err(err) = bundle.send(latency) {
+                    println!("error sending OSC message: {}", err);
+                }
+
+                thread::sleep(duration);
+            }
+
+            pattern.lock().unwrap().position = None;
+        });
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.store(false, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::config;
+use crate::status;
+use crate::ui;
+use state;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static STATE: state::Storage<Mutex<State>> = state::Storage::new();
+// the running server, kept here so it can be killed when scthing exits
+static CHILD: state::Storage<Mutex<Option<Child>>> = state::Storage::new();
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum State {
+    Starting,
+    Running,
+    // waiting to restart at the given time
+    Restarting(Instant),
+}
+
+impl State {
+    pub fn display(&self) -> String {
+        match self {
+            State::Starting => "starting".to_string(),
+            State::Running => "running".to_string(),
+            State::Restarting(at) => {
+                let wait = at.saturating_duration_since(Instant::now());
+                format!("retry {}s", wait.as_secs_f32().ceil())
+            },
+        }
+    }
+}
+
+// None when scthing doesn't launch the server itself
+pub fn current() -> Option<State> {
+    STATE.try_get().map(|state| state.lock().unwrap().clone())
+}
+
+fn set_state(state: State, tx: &mpsc::Sender<ui::Event>) {
+    let changed = {
+        let mut current = STATE.get().lock().unwrap();
+        let changed = *current != state;
+        *current = state;
+        changed
+    };
+
+    if changed {
+        tx.send(ui::Event::Redraw).unwrap();
+    }
+}
+
+fn log_line(log: &mut File, line: &str) {
+    if let Err(err) = writeln!(log, "scthing: {}", line) {
+        println!("error writing server log: {}", err);
+    }
+}
+
+fn launch(command: &str, args: &[String], log: &File) -> Result<Child, std::io::Error> {
+    Command::new(command)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log.try_clone()?)
+        .spawn()
+}
+
+// wait for the process to exit, marking it as running once it answers /status
+fn supervise(started: Instant, tx: &mpsc::Sender<ui::Event>) -> (String, bool) {
+    let mut ready = false;
+
+    loop {
+        let exited = match CHILD.get().lock().unwrap().as_mut() {
+            Some(child) => child.try_wait(),
+            None => return ("stopped".to_string(), ready),
+        };
+
+        match exited {
+            Ok(Some(exit)) => return (format!("exited with {}", exit), ready),
+            Ok(None) => {},
+            Err(err) => return (format!("error waiting for server: {}", err), ready),
+        }
+
+        if !ready && status::answered_since(started) {
+            ready = true;
+            set_state(State::Running, tx);
+        }
+
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+// start the server unless scthing is shutting down
+fn spawn(command: &str, args: &[String], log: &File) -> Option<Result<(), std::io::Error>> {
+    let mut child = CHILD.get().lock().unwrap();
+    if STOPPED.load(Ordering::SeqCst) {
+        return None;
+    }
+
+    Some(launch(command, args, log).map(|launched| *child = Some(launched)))
+}
+
+fn run(command: String, args: Vec<String>, mut log: File, tx: mpsc::Sender<ui::Event>) {
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        set_state(State::Starting, &tx);
+        log_line(&mut log, &format!("starting {} {}", command, args.join(" ")));
+
+        let started = Instant::now();
+        let (reason, ready) = match spawn(&command, &args, &log) {
+            Some(Ok(())) => supervise(started, &tx),
+            Some(Err(err)) => (format!("error starting {}: {}", command, err), false),
+            None => return,
+        };
+
+        if STOPPED.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // only back off further while it keeps failing before becoming ready
+        if ready {
+            backoff = MIN_BACKOFF;
+        }
+
+        println!("server {}, restarting in {}s", reason, backoff.as_secs());
+        log_line(&mut log, &reason);
+
+        let restart = Instant::now() + backoff;
+        set_state(State::Restarting(restart), &tx);
+
+        // redraw every second so the countdown stays up to date
+        while let Some(wait) = restart.checked_duration_since(Instant::now()) {
+            thread::sleep(wait.min(Duration::from_secs(1)));
+            tx.send(ui::Event::Redraw).unwrap();
+        }
+
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+// launch the configured scsynth or sclang command and keep it running
+pub fn start(server_config: &config::Server, tx: mpsc::Sender<ui::Event>) -> Option<thread::JoinHandle<()>> {
+    let command = server_config.command.clone()?;
+    let args = server_config.args.clone();
+
+    let log = OpenOptions::new().create(true).append(true).open(&server_config.log);
+    let log = match log {
+        Ok(log) => log,
+        Err(err) => {
+            println!("error opening server log {}: {}", server_config.log, err);
+            return None;
+        },
+    };
+
+    STATE.set(Mutex::new(State::Starting));
+    CHILD.set(Mutex::new(None));
+
+    Some(thread::spawn(move || {
+        run(command, args, log, tx);
+    }))
+}
+
+// kill the server on the way out so it doesn't outlive scthing holding its port
+pub fn stop() {
+    let child = match CHILD.try_get() {
+        Some(child) => child,
+        None => return,
+    };
+
+    let mut child = child.lock().unwrap();
+    STOPPED.store(true, Ordering::SeqCst);
+
+    if let Some(mut child) = child.take() {
+        if let Err(err) = child.kill() {
+            println!("error stopping server: {}", err);
+        }
+
+        if let Err(err) = child.wait() {
+            println!("error waiting for server: {}", err);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::config;
+use crate::osc;
+use crate::ui;
+use rosc::{decoder, encoder};
+use rosc::{OscMessage, OscPacket, OscType};
+use state;
+use std::io;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static MONITOR: state::Storage<Mutex<Monitor>> = state::Storage::new();
+
+// the contents of scsynth's /status.reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    pub ugens: i32,
+    pub synths: i32,
+    pub groups: i32,
+    pub synthdefs: i32,
+    pub avg_cpu: f32,
+    pub peak_cpu: f32,
+    pub sample_rate: f64,
+    pub actual_sample_rate: f64,
+}
+
+impl Status {
+    fn parse(args: &[OscType]) -> Option<Status> {
+        let int = |i: usize| match args.get(i) {
+            Some(OscType::Int(value)) => Some(*value),
+            _ => None,
+        };
+        let float = |i: usize| match args.get(i) {
+            Some(OscType::Float(value)) => Some(*value as f64),
+            Some(OscType::Double(value)) => Some(*value),
+            _ => None,
+        };
+
+        // the first arg is unused
+        Some(Status {
+            ugens: int(1)?,
+            synths: int(2)?,
+            groups: int(3)?,
+            synthdefs: int(4)?,
+            avg_cpu: float(5)? as f32,
+            peak_cpu: float(6)? as f32,
+            sample_rate: float(7)?,
+            actual_sample_rate: float(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub status: Option<Status>,
+    pub answering: bool,
+}
+
+#[derive(Debug)]
+struct Monitor {
+    health: Health,
+    last_reply: Option<Instant>,
+}
+
+// None when the server isn't monitored
+pub fn health() -> Option<Health> {
+    MONITOR.try_get().map(|monitor| monitor.lock().unwrap().health.clone())
+}
+
+// whether the server has replied to /status since `time`
+pub fn answered_since(time: Instant) -> bool {
+    MONITOR.try_get().map_or(false, |monitor| {
+        monitor.lock().unwrap().last_reply.map_or(false, |reply| reply >= time)
+    })
+}
+
+pub fn is_down() -> bool {
+    health().map_or(false, |health| !health.answering)
+}
+
+fn update(health: Health, last_reply: Option<Instant>, tx: &mpsc::Sender<ui::Event>) {
+    let changed = {
+        let mut monitor = MONITOR.get().lock().unwrap();
+        let changed = monitor.health != health;
+        monitor.health = health;
+        monitor.last_reply = last_reply;
+        changed
+    };
+
+    if changed {
+        tx.send(ui::Event::Redraw).unwrap();
+    }
+}
+
+fn poll(connection: &mut osc::Connection, interval: Duration) -> Result<Option<Status>, io::Error> {
+    let packet = OscPacket::Message(OscMessage {
+        addr: "/status".to_string(),
+        args: None,
+    });
+
+    let bytes = encoder::encode(&packet).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "error encoding /status"))?;
+    connection.send(&bytes)?;
+
+    let started = Instant::now();
+
+    // wait for the reply until it's time to ask again
+    while let Some(wait) = interval.checked_sub(started.elapsed()) {
+        if wait == Duration::from_secs(0) {
+            break;
+        }
+
+        let bytes = match connection.recv(wait)? {
+            Some(bytes) => bytes,
+            None => break,
+        };
+
+        if let Ok(OscPacket::Message(message)) = decoder::decode(&bytes) {
+            if message.addr == "/status.reply" {
+                let args = message.args.unwrap_or_default();
+                return Ok(Status::parse(&args));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn run(target: osc::Target, interval: Duration, timeout: Duration, tx: mpsc::Sender<ui::Event>) {
+    let mut connection = None;
+    let mut status = None;
+    let mut last_reply: Option<Instant> = None;
+    // only logged when it changes, a TCP server that's down fails every poll
+    let mut last_error = None;
+
+    loop {
+        let started = Instant::now();
+
+        if connection.is_none() {
+            match osc::Connection::open(&target) {
+                Ok(opened) => connection = Some(opened),
+                Err(err) => {
+                    let err = err.to_string();
+                    if last_error.as_ref() != Some(&err) {
+                        println!("error connecting to server: {}", err);
+                        last_error = Some(err);
+                    }
+                },
+            }
+        }
+
+        if let Some(open) = &mut connection {
+            match poll(open, interval) {
+                Ok(Some(reply)) => {
+                    status = Some(reply);
+                    last_reply = Some(Instant::now());
+                    last_error = None;
+                },
+                Ok(None) => {},
+                Err(err) => {
+                    let err = err.to_string();
+                    if last_error.as_ref() != Some(&err) {
+                        println!("error querying server status: {}", err);
+                        last_error = Some(err);
+                    }
+
+                    // reconnect on the next poll
+                    connection = None;
+                },
+            }
+        }
+
+        let answering = last_reply.map_or(false, |time| time.elapsed() < timeout);
+        update(Health { status: status.clone(), answering: answering }, last_reply, &tx);
+
+        if let Some(wait) = interval.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// poll the server's /status in the background, redrawing when it changes
+pub fn monitor(server_config: &config::Server, tx: mpsc::Sender<ui::Event>) -> thread::JoinHandle<()> {
+    MONITOR.set(Mutex::new(Monitor {
+        health: Health { status: None, answering: false },
+        last_reply: None,
+    }));
+
+    let conf = &*config::CONFIG.get();
+    let target = server_config.target(&conf.osc).unwrap();
+    let interval = Duration::from_secs_f32(server_config.poll_interval);
+    let timeout = Duration::from_secs_f32(server_config.timeout);
+
+    thread::spawn(move || {
+        run(target, interval, timeout, tx);
+    })
+}
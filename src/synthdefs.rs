@@ -0,0 +1,192 @@
+pub mod format;
+
+use crate::config;
+use crate::osc;
+use crate::status;
+use crate::ui;
+use rosc::{decoder, encoder};
+use rosc::{OscMessage, OscPacket, OscType};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+// patches wait for this before starting when there are synthdefs to load,
+// it's set once they've all been sent and cleared again while reloading
+static LOADED: AtomicBool = AtomicBool::new(false);
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// how long to wait for /status before trying to load anyway
+const STATUS_WAIT: Duration = Duration::from_secs(10);
+
+pub fn is_loaded() -> bool {
+    let conf = &*config::CONFIG.get();
+    let configured = conf.server.as_ref().map_or(false, |server| !server.synthdefs.is_empty());
+    !configured || LOADED.load(Ordering::SeqCst)
+}
+
+// the newest modification time of a file or the .scsyndef files in a directory
+fn modified(path: &Path) -> Option<SystemTime> {
+    if path.is_dir() {
+        fs::read_dir(path).ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "scsyndef"))
+            .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+            .max()
+    } else {
+        fs::metadata(path).ok()?.modified().ok()
+    }
+}
+
+// send a command and wait for its /done or /fail
+fn request(connection: &mut osc::Connection, command: &str, args: Vec<OscType>) -> Result<(), String> {
+    let packet = OscPacket::Message(OscMessage {
+        addr: command.to_string(),
+        args: Some(args),
+    });
+
+    let bytes = encoder::encode(&packet).map_err(|_| format!("error encoding {}", command))?;
+    connection.send(&bytes).map_err(|err| err.to_string())?;
+
+    let started = Instant::now();
+
+    while let Some(wait) = REPLY_TIMEOUT.checked_sub(started.elapsed()) {
+        if wait == Duration::from_secs(0) {
+            break;
+        }
+
+        let bytes = match connection.recv(wait).map_err(|err| err.to_string())? {
+            Some(bytes) => bytes,
+            None => break,
+        };
+
+        let message = match decoder::decode(&bytes) {
+            Ok(OscPacket::Message(message)) => message,
+            _ => continue,
+        };
+
+        let args = message.args.unwrap_or_default();
+        if args.get(0) != Some(&OscType::String(command.to_string())) {
+            continue;
+        }
+
+        match message.addr.as_str() {
+            "/done" => return Ok(()),
+            "/fail" => {
+                return match args.get(1) {
+                    Some(OscType::String(err)) => Err(err.clone()),
+                    _ => Err(format!("{} failed", command)),
+                };
+            },
+            _ => {},
+        }
+    }
+
+    Err(format!("no reply to {}", command))
+}
+
+// files are sent to the server, directories are loaded by the server itself
+// so they have to be on the same machine
+fn load(connection: &mut osc::Connection, path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        let pattern = path.join("*.scsyndef").to_string_lossy().to_string();
+        request(connection, "/d_load", vec![OscType::String(pattern)])
+    } else {
+        let bytes = fs::read(path).map_err(|err| err.to_string())?;
+        request(connection, "/d_recv", vec![OscType::Blob(bytes)])
+    }
+}
+
+fn load_all(target: &osc::Target, paths: &[PathBuf], tx: &mpsc::Sender<ui::Event>) {
+    LOADED.store(false, Ordering::SeqCst);
+    tx.send(ui::Event::Redraw).unwrap();
+
+    let mut failures = vec![];
+    let mut connection = osc::Connection::open(target).map_err(|err| err.to_string());
+
+    for path in paths {
+        let loaded = match &mut connection {
+            Ok(connection) => load(connection, path),
+            Err(err) => Err(err.clone()),
+        };
+
+        if let Err(err) = loaded {
+            println!("error loading synthdefs from {}: {}", path.display(), err);
+            failures.push((path, err));
+        }
+    }
+
+    LOADED.store(true, Ordering::SeqCst);
+
+    match failures.first() {
+        Some((path, err)) => {
+            let name = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+            let lines = vec![
+                format!("{} synthdef error(s)", failures.len()),
+                name,
+                err.clone(),
+            ];
+            tx.send(ui::Event::Notice(lines)).unwrap();
+        },
+        None => tx.send(ui::Event::Redraw).unwrap(),
+    }
+}
+
+fn run(target: osc::Target, paths: Vec<PathBuf>, tx: mpsc::Sender<ui::Event>) {
+    let started = Instant::now();
+    let mut answering = false;
+    let mut attempted = false;
+    let mut loaded: Vec<Option<SystemTime>> = vec![];
+
+    // reload whenever the server comes (back) up or the files change
+    loop {
+        let was_answering = answering;
+        answering = status::health().map_or(false, |health| health.answering);
+
+        // rather than holding patches back forever when /status goes
+        // unanswered, try once anyway so any error gets shown
+        if !answering && !attempted && started.elapsed() >= STATUS_WAIT {
+            println!("server isn't answering /status, loading synthdefs anyway");
+            load_all(&target, &paths, &tx);
+            loaded = paths.iter().map(|path| modified(path)).collect();
+            attempted = true;
+        }
+
+        if answering {
+            attempted = true;
+            let current = paths.iter().map(|path| modified(path)).collect::<Vec<_>>();
+
+            if !was_answering || current != loaded {
+                load_all(&target, &paths, &tx);
+                loaded = current;
+            }
+        }
+
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+// load the configured SynthDefs once the server is up
+pub fn start(server_config: &config::Server, tx: mpsc::Sender<ui::Event>) -> Option<thread::JoinHandle<()>> {
+    if server_config.synthdefs.is_empty() {
+        return None;
+    }
+
+    let conf = &*config::CONFIG.get();
+    let target = match server_config.target(&conf.osc) {
+        Ok(target) => target,
+        Err(err) => {
+            println!("error loading synthdefs: {}", err);
+            return None;
+        },
+    };
+
+    let paths = server_config.synthdefs.iter().map(PathBuf::from).collect();
+
+    Some(thread::spawn(move || {
+        run(target, paths, tx);
+    }))
+}
@@ -0,0 +1,278 @@
+// reads the controls out of compiled SynthDef files (version 1 and 2)
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "IO error: {}", error)]
+    IoError {
+        error: io::Error,
+    },
+    #[fail(display = "invalid SynthDef file: {}", reason)]
+    FormatError {
+        reason: String,
+    },
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::IoError { error: error }
+    }
+}
+
+fn format_error(reason: &str) -> Error {
+    Error::FormatError { reason: reason.to_string() }
+}
+
+#[derive(Debug, Clone)]
+pub struct Control {
+    pub name: String,
+    pub value: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SynthDef {
+    pub name: String,
+    pub controls: Vec<Control>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    version: i32,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.bytes.get(self.pos..self.pos + len)
+            .ok_or_else(|| format_error("unexpected end of file"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, Error> {
+        let bytes = self.take(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.i32()? as u32))
+    }
+
+    fn pstring(&mut self) -> Result<String, Error> {
+        let len = self.u8()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).to_string())
+    }
+
+    // counts and indices are 16-bit in version 1 and 32-bit in version 2
+    fn int(&mut self) -> Result<i32, Error> {
+        if self.version >= 2 { self.i32() } else { Ok(self.i16()? as i32) }
+    }
+
+    fn index(&mut self) -> Result<usize, Error> {
+        let index = self.int()?;
+        if index < 0 {
+            return Err(format_error("negative count"));
+        }
+        Ok(index as usize)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.take(len).map(|_| ())
+    }
+}
+
+fn read_def(reader: &mut Reader) -> Result<SynthDef, Error> {
+    let name = reader.pstring()?;
+
+    let constants = reader.index()?;
+    reader.skip(constants * 4)?;
+
+    let num_values = reader.index()?;
+    let values = (0..num_values).map(|_| reader.f32()).collect::<Result<Vec<f32>, Error>>()?;
+
+    let num_names = reader.index()?;
+    let mut controls = vec![];
+    for _ in 0..num_names {
+        let name = reader.pstring()?;
+        let index = reader.index()?;
+
+        // array controls only get their first value
+        let value = *values.get(index).ok_or_else(|| format_error("control index out of range"))?;
+        controls.push(Control { name: name, value: value });
+    }
+
+    // the rest is only read to get to the next def in the file
+    let ugens = reader.index()?;
+    for _ in 0..ugens {
+        reader.pstring()?;
+        reader.u8()?;
+        let inputs = reader.index()?;
+        let outputs = reader.index()?;
+        reader.i16()?;
+
+        // the ugen (-1 for constants) and output index of each input
+        for _ in 0..inputs {
+            reader.int()?;
+            reader.int()?;
+        }
+        reader.skip(outputs)?;
+    }
+
+    let variants = reader.i16()? as usize;
+    for _ in 0..variants {
+        reader.pstring()?;
+        reader.skip(num_values * 4)?;
+    }
+
+    Ok(SynthDef {
+        name: name,
+        controls: controls,
+    })
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Vec<SynthDef>, Error> {
+    let mut reader = Reader { bytes: bytes, pos: 0, version: 0 };
+
+    if reader.take(4)? != b"SCgf" {
+        return Err(format_error("missing SCgf header"));
+    }
+
+    reader.version = reader.i32()?;
+    if reader.version != 1 && reader.version != 2 {
+        return Err(format_error(&format!("unsupported version {}", reader.version)));
+    }
+
+    let count = reader.i16()?;
+    (0..count).map(|_| read_def(&mut reader)).collect()
+}
+
+// a .scsyndef file or a directory of them
+pub fn read(path: &Path) -> Result<Vec<SynthDef>, Error> {
+    if !path.is_dir() {
+        return parse(&fs::read(path)?);
+    }
+
+    let mut defs = vec![];
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "scsyndef") {
+            defs.extend(parse(&fs::read(&path)?)?);
+        }
+    }
+
+    Ok(defs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pstring(bytes: &mut Vec<u8>, s: &str) {
+        bytes.push(s.len() as u8);
+        bytes.extend_from_slice(s.as_bytes());
+    }
+
+    fn int(bytes: &mut Vec<u8>, version: i32, value: i32) {
+        if version >= 2 {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        } else {
+            bytes.extend_from_slice(&(value as i16).to_be_bytes());
+        }
+    }
+
+    // a def with a constant, `freq` and `amp` controls, a Control and a
+    // SinOsc ugen and a variant
+    fn def(bytes: &mut Vec<u8>, version: i32, name: &str) {
+        pstring(bytes, name);
+
+        int(bytes, version, 1);
+        bytes.extend_from_slice(&0.0f32.to_be_bytes());
+
+        int(bytes, version, 2);
+        bytes.extend_from_slice(&440.0f32.to_be_bytes());
+        bytes.extend_from_slice(&0.5f32.to_be_bytes());
+
+        int(bytes, version, 2);
+        pstring(bytes, "freq");
+        int(bytes, version, 0);
+        pstring(bytes, "amp");
+        int(bytes, version, 1);
+
+        int(bytes, version, 2);
+
+        pstring(bytes, "Control");
+        bytes.push(1);
+        int(bytes, version, 0);
+        int(bytes, version, 2);
+        bytes.extend_from_slice(&0i16.to_be_bytes());
+        bytes.extend_from_slice(&[1, 1]);
+
+        pstring(bytes, "SinOsc");
+        bytes.push(2);
+        int(bytes, version, 2);
+        int(bytes, version, 1);
+        bytes.extend_from_slice(&0i16.to_be_bytes());
+        int(bytes, version, 0);
+        int(bytes, version, 0);
+        int(bytes, version, -1);
+        int(bytes, version, 0);
+        bytes.push(2);
+
+        bytes.extend_from_slice(&1i16.to_be_bytes());
+        pstring(bytes, "low");
+        bytes.extend_from_slice(&220.0f32.to_be_bytes());
+        bytes.extend_from_slice(&0.25f32.to_be_bytes());
+    }
+
+    fn file(version: i32) -> Vec<u8> {
+        let mut bytes = b"SCgf".to_vec();
+        bytes.extend_from_slice(&version.to_be_bytes());
+        bytes.extend_from_slice(&2i16.to_be_bytes());
+        def(&mut bytes, version, "sine");
+        def(&mut bytes, version, "other");
+        bytes
+    }
+
+    fn check(defs: Vec<SynthDef>) {
+        let names = defs.iter().map(|def| def.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["sine", "other"]);
+
+        for def in defs.iter() {
+            let controls = def.controls.iter().map(|c| (c.name.as_str(), c.value)).collect::<Vec<_>>();
+            assert_eq!(controls, vec![("freq", 440.0), ("amp", 0.5)]);
+        }
+    }
+
+    #[test]
+    fn parses_version_1() {
+        check(parse(&file(1)).unwrap());
+    }
+
+    #[test]
+    fn parses_version_2() {
+        check(parse(&file(2)).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(parse(b"nope").is_err());
+
+        let mut bytes = file(3);
+        assert!(parse(&bytes).is_err());
+
+        bytes = file(2);
+        bytes.truncate(bytes.len() - 1);
+        assert!(parse(&bytes).is_err());
+    }
+}
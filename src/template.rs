@@ -0,0 +1,307 @@
+use crate::config;
+use crate::osc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+// node IDs below 1000 are left to SC
+static NEXT_INSTANCE: AtomicI32 = AtomicI32::new(1000);
+
+pub const PATCH_PLACEHOLDERS: &[&str] = &["patch", "instance"];
+pub const PARAM_PLACEHOLDERS: &[&str] = &["patch", "instance", "param", "value"];
+pub const NOTE_PLACEHOLDERS: &[&str] = &["patch", "instance", "value"];
+
+#[derive(Debug, Clone)]
+enum Arg {
+    Literal(osc::Type),
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    addr: String,
+    args: Vec<Arg>,
+}
+
+#[derive(Debug, Default)]
+pub struct Vars<'a> {
+    pub patch: &'a str,
+    pub instance: i32,
+    pub param: Option<&'a str>,
+    pub value: Option<osc::Type>,
+}
+
+impl<'a> Vars<'a> {
+    fn get(&self, name: &str) -> Option<osc::Type> {
+        match name {
+            "patch" => Some(osc::Type::String(self.patch.to_string())),
+            "instance" => Some(osc::Type::Int(self.instance)),
+            "param" => self.param.map(|param| osc::Type::String(param.to_string())),
+            "value" => self.value.clone(),
+            _ => None,
+        }
+    }
+}
+
+fn placeholders(text: &str) -> Result<Vec<&str>, String> {
+    let mut names = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}')
+            .ok_or_else(|| format!("unclosed placeholder in \"{}\"", text))?;
+        names.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+
+    if rest.contains('}') {
+        return Err(format!("unopened placeholder in \"{}\"", text));
+    }
+
+    Ok(names)
+}
+
+fn to_text(value: &osc::Type) -> String {
+    match value {
+        osc::Type::Int(value) => value.to_string(),
+        osc::Type::Float(value) => value.to_string(),
+        osc::Type::Bool(value) => value.to_string(),
+        osc::Type::String(value) => value.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn interpolate(text: &str, vars: &Vars) -> String {
+    let mut result = text.to_string();
+
+    for name in placeholders(text).unwrap_or_default() {
+        let value = vars.get(name).map(|v| to_text(&v)).unwrap_or_default();
+        result = result.replace(&format!("{{{}}}", name), &value);
+    }
+
+    result
+}
+
+impl Template {
+    pub fn new(addr: &str, args: Vec<&str>) -> Self {
+        Template {
+            addr: addr.to_string(),
+            args: args.into_iter().map(|arg| Arg::Text(arg.to_string())).collect(),
+        }
+    }
+
+    pub fn parse(message_config: &config::Message, allowed: &[&str]) -> Result<Self, String> {
+        let mut texts = vec![message_config.addr.as_str()];
+
+        let args = message_config.args.iter().map(|arg| {
+            match arg {
+                config::Arg::String(text) => {
+                    texts.push(text);
+                    Arg::Text(text.clone())
+                },
+                other => Arg::Literal(other.to_osc()),
+            }
+        }).collect();
+
+        for text in texts {
+            for name in placeholders(text)? {
+                if !allowed.contains(&name) {
+                    return Err(format!("unknown placeholder {{{}}} in \"{}\"", name, text));
+                }
+            }
+        }
+
+        Ok(Template {
+            addr: message_config.addr.clone(),
+            args: args,
+        })
+    }
+
+    // a string arg that's exactly one placeholder keeps the variable's type,
+    // anything else is interpolated into a string
+    pub fn render(&self, vars: &Vars) -> (String, Vec<osc::Type>) {
+        let args = self.args.iter().map(|arg| {
+            match arg {
+                Arg::Literal(value) => value.clone(),
+                Arg::Text(text) => {
+                    let names = placeholders(text).unwrap_or_default();
+                    let whole = names.len() == 1 && text.len() == names[0].len() + 2;
+
+                    match vars.get(names.get(0).unwrap_or(&"")) {
+                        Some(value) if whole => value,
+                        _ => osc::Type::String(interpolate(text, vars)),
+                    }
+                },
+            }
+        }).collect();
+
+        (interpolate(&self.addr, vars), args)
+    }
+
+    pub fn add(&self, bundle: &mut osc::Bundle, targets: &[osc::Target], vars: &Vars) {
+        let (addr, args) = self.render(vars);
+        let args = if args.is_empty() { None } else { Some(args) };
+
+        bundle.add(targets, &addr, args);
+    }
+}
+
+pub type Shared = Arc<Messages>;
+
+// the OSC messages for a patch, along with its current instance
+#[derive(Debug)]
+pub struct Messages {
+    patch: String,
+    // the patch's place in the menus, names can repeat across menus
+    path: String,
+    instance: AtomicI32,
+    targets: Vec<osc::Target>,
+    start: Template,
+    stop: Template,
+    set: Template,
+    note: Template,
+}
+
+impl Messages {
+    pub fn new(path: &str, patch_config: &config::Patch) -> Self {
+        let conf = &*config::CONFIG.get();
+        let osc_config = patch_config.osc.as_ref();
+        let template = |message: Option<&config::Message>, allowed, default| {
+            message.map(|m| Template::parse(m, allowed).unwrap()).unwrap_or(default)
+        };
+
+        Messages {
+            patch: patch_config.name.clone(),
+            path: path.to_string(),
+            instance: AtomicI32::new(0),
+            targets: conf.osc.resolve(patch_config.targets.as_ref()),
+            start: template(
+                osc_config.and_then(|o| o.start.as_ref()),
+                PATCH_PLACEHOLDERS,
+                Template::new("start", vec!["{patch}"]),
+            ),
+            stop: template(
+                osc_config.and_then(|o| o.stop.as_ref()),
+                PATCH_PLACEHOLDERS,
+                Template::new("stop", vec![]),
+            ),
+            set: template(
+                osc_config.and_then(|o| o.set.as_ref()),
+                PARAM_PLACEHOLDERS,
+                Template::new("set", vec!["{param}", "{value}"]),
+            ),
+            note: template(
+                osc_config.and_then(|o| o.note.as_ref()),
+                NOTE_PLACEHOLDERS,
+                Template::new("note", vec!["{patch}", "{value}"]),
+            ),
+        }
+    }
+
+    pub fn shared(self) -> Shared {
+        Arc::new(self)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn vars(&self) -> Vars<'_> {
+        Vars {
+            patch: &self.patch,
+            instance: self.instance.load(Ordering::SeqCst),
+            ..Default::default()
+        }
+    }
+
+    pub fn start(&self, bundle: &mut osc::Bundle) {
+        let instance = NEXT_INSTANCE.fetch_add(1, Ordering::SeqCst);
+        self.instance.store(instance, Ordering::SeqCst);
+        self.start.add(bundle, &self.targets, &self.vars());
+    }
+
+    pub fn stop(&self, bundle: &mut osc::Bundle) {
+        self.stop.add(bundle, &self.targets, &self.vars());
+    }
+
+    // `template` and `targets` override the patch's set message and targets
+    // for a single param
+    pub fn set(
+        &self,
+        bundle: &mut osc::Bundle,
+        template: Option<&Template>,
+        targets: Option<&Vec<osc::Target>>,
+        param: &str,
+        value: osc::Type,
+    ) {
+        let vars = Vars {
+            param: Some(param),
+            value: Some(value),
+            ..self.vars()
+        };
+
+        let targets = targets.unwrap_or(&self.targets);
+        template.unwrap_or(&self.set).add(bundle, targets, &vars);
+    }
+
+    pub fn note(&self, bundle: &mut osc::Bundle, value: f32) {
+        let vars = Vars {
+            value: Some(osc::Type::Float(value)),
+            ..self.vars()
+        };
+
+        self.note.add(bundle, &self.targets, &vars);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(addr: &str, args: Vec<config::Arg>) -> config::Message {
+        config::Message {
+            addr: addr.to_string(),
+            args: args,
+        }
+    }
+
+    fn text(arg: &str) -> config::Arg {
+        config::Arg::String(arg.to_string())
+    }
+
+    #[test]
+    fn finds_placeholders() {
+        assert_eq!(placeholders("/{patch}/set/{param}").unwrap(), vec!["patch", "param"]);
+        assert_eq!(placeholders("/plain").unwrap(), Vec::<&str>::new());
+        assert!(placeholders("/{patch").is_err());
+        assert!(placeholders("/patch}").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_placeholders() {
+        assert!(Template::parse(&message("/{patch}", vec![text("{instance}")]), PATCH_PLACEHOLDERS).is_ok());
+        assert!(Template::parse(&message("/{patch}", vec![text("{value}")]), PATCH_PLACEHOLDERS).is_err());
+        assert!(Template::parse(&message("/{nope}", vec![]), PARAM_PLACEHOLDERS).is_err());
+        assert!(Template::parse(&message("/set", vec![text("{value")]), PARAM_PLACEHOLDERS).is_err());
+    }
+
+    #[test]
+    fn renders_args() {
+        let args = vec![text("{value}"), text("{param}-{value}"), text("{instance}"), config::Arg::Float(0.5)];
+        let template = Template::parse(&message("/{patch}/set", args), PARAM_PLACEHOLDERS).unwrap();
+        let vars = Vars {
+            patch: "sine",
+            instance: 1000,
+            param: Some("freq"),
+            value: Some(osc::Type::Int(3)),
+        };
+
+        let (addr, args) = template.render(&vars);
+        assert_eq!(addr, "/sine/set");
+        assert_eq!(args, vec![
+            osc::Type::Int(3),
+            osc::Type::String("freq-3".to_string()),
+            osc::Type::Int(1000),
+            osc::Type::Float(0.5),
+        ]);
+    }
+}
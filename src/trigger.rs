@@ -0,0 +1,54 @@
+use crate::config;
+use crate::osc;
+
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    targets: Vec<osc::Target>,
+    addr: String,
+    args: Vec<osc::Type>,
+    momentary: bool,
+}
+
+impl Trigger {
+    // actions without their own targets use `fallback` (their patch's targets)
+    pub fn new(action_config: &config::Action, fallback: Option<&Vec<String>>) -> Self {
+        let conf = &*config::CONFIG.get();
+        let targets = action_config.targets.as_ref().or(fallback);
+
+        Trigger {
+            targets: conf.osc.resolve(targets),
+            addr: action_config.addr.clone(),
+            args: action_config.args.iter().map(|arg| arg.to_osc()).collect(),
+            momentary: action_config.momentary,
+        }
+    }
+
+    pub fn is_momentary(&self) -> bool {
+        self.momentary
+    }
+
+    pub fn fire(&self) {
+        self.send(self.args.clone());
+    }
+
+    // momentary actions append 1 while the button is held and 0 on release
+    pub fn down(&self) {
+        let mut args = self.args.clone();
+        args.push(osc::Type::Int(1));
+        self.send(args);
+    }
+
+    pub fn up(&self) {
+        let mut args = self.args.clone();
+        args.push(osc::Type::Int(0));
+        self.send(args);
+    }
+
+    fn send(&self, args: Vec<osc::Type>) {
+        let args = if args.is_empty() { None } else { Some(args) };
+
+        if let Err(err) = osc::send(&self.targets, &self.addr, args) {
+            println!("error sending OSC message: {}", err);
+        }
+    }
+}
@@ -1,28 +1,59 @@
+pub mod font;
 pub mod menu;
 pub mod param;
 pub mod patch;
+pub mod sequencer;
+pub mod status;
 
+use crate::automation::{self, Automation};
 use crate::config;
-use crate::ui::menu::{Menu, MenuItem};
+use crate::modulation::{Lfo, Modulation, Modulator};
+use crate::osc;
+use crate::preset::{self, Preset};
+use crate::remote;
+use crate::sequencer::{Pattern, Track, TrackKind};
+use crate::template::{self, Messages, Template};
+use crate::trigger::Trigger;
+use crate::ui::menu::{Detail, Menu, MenuItem};
 use crate::ui::param::Param;
 use crate::ui::patch::Patch;
-use font_kit::font::Font;
+use crate::ui::sequencer::Sequencer;
+use crate::ui::status::Status;
 use raqote;
-use std::collections::HashMap;
-use std::sync::Arc;
-
-pub static FONT_BYTES: &'static [u8; 92600] = include_bytes!("ui/fonts/inconsolata.ttf");
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 const BACKGROUND: raqote::SolidSource = raqote::SolidSource { r: 0x00, g: 0x00, b: 0x00, a: 0x00 };
 const FOREGROUND: raqote::SolidSource = raqote::SolidSource { r: 0xFF, g: 0xFF, b: 0xFF, a: 0xFF };
 const FOREGROUND_SOURCE: raqote::Source = raqote::Source::Solid(FOREGROUND);
 
+const SCROLLBAR_WIDTH: f32 = 2.0;
+
+const ELLIPSIS: &str = "…";
+
+// labels that don't fit scroll across, waiting at either end
+const MARQUEE_SPEED: f32 = 20.0;
+const MARQUEE_PAUSE: Duration = Duration::from_millis(1000);
+const MARQUEE_FRAME: Duration = Duration::from_millis(50);
+
 pub type ScreenId = u32;
 
 pub enum Input {
     Right,
     Left,
     Press,
+    LongPress,
+    Down,
+    Release,
+}
+
+pub enum Event {
+    Input(Input),
+    Remote(remote::Request),
+    Notice(Vec<String>),
+    Redraw,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -37,14 +68,37 @@ pub trait Screen {
 
     fn load(&mut self) {}
     fn unload(&mut self) {}
+
+    // how long after rendering the screen wants `tick` called, for anything
+    // that changes over time
+    fn next_tick(&self) -> Option<Duration> { None }
+    // returns whether the screen needs rendering again
+    fn tick(&mut self) -> bool { true }
 }
 
 type ScreenT = Box<dyn Screen>;
 
+// what's needed to reload a patch's preset while it's running
+struct PatchState {
+    params: Vec<crate::param::Shared>,
+    running: Arc<AtomicBool>,
+    pattern: Option<Arc<Mutex<Pattern>>>,
+    automations: Vec<(String, automation::Shared)>,
+}
+
 pub struct UI {
     screens: HashMap<ScreenId, ScreenT>,
     stack: Vec<ScreenId>,
     next_id: ScreenId,
+    // menus and patches by path ("menu/patch"), params are "menu/patch/param"
+    paths: BTreeMap<String, ScreenId>,
+    params: BTreeMap<String, crate::param::Shared>,
+    patches: HashMap<String, PatchState>,
+    // shown over the current screen until the button is pressed
+    notice: Option<Vec<String>>,
+    // whether anything changed since the last render
+    dirty: bool,
+    next_tick: Option<Instant>,
 }
 
 impl UI {
@@ -53,6 +107,12 @@ impl UI {
             screens: HashMap::new(),
             stack: vec![],
             next_id: 0,
+            paths: BTreeMap::new(),
+            params: BTreeMap::new(),
+            patches: HashMap::new(),
+            notice: None,
+            dirty: true,
+            next_tick: None,
         }
     }
 
@@ -76,16 +136,203 @@ impl UI {
         self.stack.pop();
     }
 
+    fn is_patch_screen(&self, screen_id: ScreenId) -> bool {
+        self.patches.keys().any(|path| self.paths.get(path) == Some(&screen_id))
+    }
+
+    fn enter(&mut self, screen_id: ScreenId) {
+        if !crate::synthdefs::is_loaded() && self.is_patch_screen(screen_id) {
+            self.notify(vec!["synthdefs are".to_string(), "still loading".to_string()]);
+            return;
+        }
+
+        self.push_screen(screen_id);
+
+        if let Some(screen) = self.current_screen() {
+            screen.load();
+        }
+    }
+
+    fn leave(&mut self) {
+        if let Some(screen) = self.current_screen() {
+            screen.unload();
+        }
+
+        self.pop_screen();
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.paths.keys().chain(self.params.keys()).cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn param(&self, path: &str) -> Option<crate::param::Shared> {
+        self.params.get(path).cloned()
+    }
+
+    pub fn is_patch(&self, path: &str) -> bool {
+        self.patches.contains_key(path)
+    }
+
+    pub fn notify(&mut self, lines: Vec<String>) {
+        self.notice = Some(lines);
+    }
+
+    // navigate from the root menu to a menu or patch, starting it
+    pub fn open(&mut self, path: &str) -> Result<(), String> {
+        if self.is_patch(path) && !crate::synthdefs::is_loaded() {
+            return Err("synthdefs are still loading".to_string());
+        }
+
+        let mut ids = vec![];
+        let mut prefix = String::new();
+
+        for name in path.split('/') {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(name);
+
+            let id = self.paths.get(&prefix).ok_or_else(|| format!("unknown path {}", prefix))?;
+            ids.push(*id);
+        }
+
+        if self.stack.len() > 0 && self.stack[1..] == ids[..] {
+            return Ok(());
+        }
+
+        while self.stack.len() > 1 {
+            self.leave();
+        }
+
+        for id in ids {
+            self.enter(id);
+        }
+
+        Ok(())
+    }
+
+    // navigate back out of a menu or patch, stopping it
+    pub fn close(&mut self, path: &str) -> Result<(), String> {
+        let id = self.paths.get(path).ok_or_else(|| format!("unknown path {}", path))?;
+        let index = self.stack.iter().position(|i| i == id)
+            .ok_or_else(|| format!("{} isn't open", path))?;
+
+        while self.stack.len() > index {
+            self.leave();
+        }
+
+        Ok(())
+    }
+
+    pub fn load_preset(&mut self, path: &str) -> Result<(), String> {
+        let patch = self.patches.get(path).ok_or_else(|| format!("unknown patch {}", path))?;
+        let preset = preset::load(path).ok_or_else(|| format!("no preset for {}", path))?;
+
+        // a running patch gets all the recalled values at once
+        let mut bundle = osc::Bundle::new();
+        for param in patch.params.iter() {
+            let mut param = param.lock().unwrap();
+            if let Some(value) = preset.params.get(&param.name) {
+                param.value = param.clamp(*value);
+                param.add(&mut bundle);
+            }
+        }
+
+        if patch.running.load(Ordering::SeqCst) {
+            let conf = &*config::CONFIG.get();
+            if let Err(err) = bundle.send(conf.osc.latency()) {
+                println!("error sending OSC message: {}", err);
+            }
+        }
+
+        if let (Some(pattern), Some(saved)) = (&patch.pattern, &preset.pattern) {
+            pattern.lock().unwrap().merge(saved);
+        }
+
+        for (name, automation) in patch.automations.iter() {
+            automation.lock().unwrap().load(preset.automations.get(name).cloned());
+        }
+
+        Ok(())
+    }
+
     pub fn render(&mut self, target: &mut raqote::DrawTarget) {
-        match self.current_screen() {
-            Some(screen) => {
-                screen.render(target);
+        match &self.notice {
+            Some(lines) => render_lines(lines.clone(), target),
+            None => {
+                if let Some(screen) = self.current_screen() {
+                    screen.render(target);
+                }
+
+                if crate::status::is_down() {
+                    render_warning(target);
+                }
             },
-            None => {}
         }
+
+        self.dirty = false;
+        self.schedule();
+    }
+
+    pub fn needs_render(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn next_tick(&self) -> Option<Instant> {
+        self.next_tick
+    }
+
+    fn schedule(&mut self) {
+        let after = match self.notice {
+            Some(_) => None,
+            None => self.current_screen().and_then(|screen| screen.next_tick()),
+        };
+
+        self.next_tick = after.map(|after| Instant::now() + after);
+    }
+
+    // tick the current screen if it's due
+    pub fn tick(&mut self) {
+        if !self.next_tick.map_or(false, |at| at <= Instant::now()) {
+            return;
+        }
+
+        if let Some(screen) = self.current_screen() {
+            self.dirty |= screen.tick();
+        }
+        self.schedule();
+    }
+
+    // anything coming from the input, remote or background threads could
+    // change what's on screen
+    pub fn event(&mut self, event: Event) {
+        match event {
+            Event::Input(input) => self.handle(input),
+            Event::Remote(request) => remote::handle(self, request),
+            Event::Notice(lines) => self.notify(lines),
+            Event::Redraw => {},
+        }
+
+        self.dirty = true;
     }
 
     pub fn handle(&mut self, input: Input) {
+        // a notice swallows input until it's dismissed, apart from releases
+        // so that a held trigger doesn't get stuck down
+        if self.notice.is_some() {
+            match input {
+                Input::Release => {},
+                Input::Press => {
+                    self.notice = None;
+                    return;
+                },
+                _ => return,
+            }
+        }
+
         let action = match self.current_screen() {
             Some(screen) => { screen.handle(input) },
             None => { None }
@@ -93,96 +340,386 @@ impl UI {
 
         if let Some(action) = action {
             match action {
-                Action::Push(screen_id) => {
-                    self.push_screen(screen_id);
-
-                    if let Some(screen) = self.current_screen() {
-                        screen.load();
-                    }
-                },
-                Action::Pop => {
-                    if let Some(screen) = self.current_screen() {
-                        screen.unload();
-                    }
-
-                    self.pop_screen();
-                },
+                Action::Push(screen_id) => self.enter(screen_id),
+                Action::Pop => self.leave(),
             }
         }
     }
 }
 
+// the distance between the baselines of two lines of text
+fn line_height() -> f32 {
+    let conf = &*config::CONFIG.get();
+    font::with(|font| (font.ascent + font.descent).ceil()) + conf.display.line_spacing
+}
+
+// the baseline of a row of text
+fn line_y(row: usize) -> f32 {
+    let conf = &*config::CONFIG.get();
+    conf.display.margins.top + font::with(|font| font.ascent.ceil()) + line_height() * row as f32
+}
+
+// how much horizontal space text has between the margins
+fn text_width(target: &raqote::DrawTarget) -> f32 {
+    let margins = &config::CONFIG.get().display.margins;
+    target.width() as f32 - margins.left - margins.right
+}
+
 fn render_lines(lines: Vec<String>, target: &mut raqote::DrawTarget) {
     target.clear(BACKGROUND);
 
-    let draw_options = raqote::DrawOptions::new();
-    let font = Font::from_bytes(Arc::new(FONT_BYTES.to_vec()), 0).unwrap();
+    let left = config::CONFIG.get().display.margins.left;
+    let width = text_width(target);
 
-    let mut draw_text = |text: &str, mut start: raqote::Point| {
-        let mut ids = Vec::new();
-        let mut positions = Vec::new();
-        for c in text.chars() {
-            let id = font.glyph_for_char(c).unwrap();
-            ids.push(id);
-            positions.push(start);
-            start += font.advance(id).unwrap() / 70.0;
+    font::with(|font| {
+        for (i, line) in lines.iter().enumerate() {
+            font.draw(target, &truncate(font, &line, width), raqote::Point::new(left, line_y(i)));
         }
+    });
+}
 
-        target.draw_glyphs(&font, 14.0, &ids, &positions, &FOREGROUND_SOURCE, &draw_options);
-    };
+// `text` cut short with an ellipsis if it's wider than `width`
+fn truncate(font: &font::Font, text: &str, width: f32) -> String {
+    if font.width(text) <= width {
+        return text.to_string();
+    }
+
+    let available = width - font.width(ELLIPSIS);
+    let mut used = 0.0;
+    let kept = text.chars().take_while(|c| {
+        used += font.glyph(*c).advance;
+        used <= available
+    }).collect::<String>();
 
-    let offset = 2.0;
-    let line_height = 14.0;
+    format!("{}{}", kept.trim_end(), ELLIPSIS)
+}
+
+// how far a label `overflow` pixels too wide has scrolled after `elapsed`
+fn marquee_offset(overflow: f32, elapsed: Duration) -> f32 {
+    let scroll = overflow / MARQUEE_SPEED;
+    let pause = MARQUEE_PAUSE.as_secs_f32();
+    let t = elapsed.as_secs_f32() % (pause + scroll + pause);
+
+    ((t - pause) * MARQUEE_SPEED).max(0.0).min(overflow)
+}
+
+// a label starting at `x` that fits in `width`, truncated or scrolled by
+// the marquee for `scrolling` if it doesn't, returns whether it didn't fit
+fn render_label(
+    target: &mut raqote::DrawTarget,
+    font: &font::Font,
+    text: &str,
+    x: f32,
+    row: usize,
+    width: f32,
+    scrolling: Option<Duration>,
+) -> bool {
+    let y = line_y(row);
+    let overflow = font.width(text) - width;
+
+    if overflow <= 0.0 {
+        font.draw(target, text, raqote::Point::new(x, y));
+        return false;
+    }
 
-    for (i, line) in lines.iter().enumerate() {
-        let point = raqote::Point::new(0.0, (line_height * (i + 1) as f32) + offset);
-        draw_text(&line, point);
+    match scrolling {
+        Some(elapsed) => {
+            let offset = marquee_offset(overflow, elapsed);
+            font.draw_clipped(target, text, raqote::Point::new(x - offset, y), x, x + width);
+        },
+        None => font.draw(target, &truncate(font, text, width), raqote::Point::new(x, y)),
     }
+
+    true
+}
+
+// where right-aligned text ends, clear of the scrollbar
+fn details_right(target: &raqote::DrawTarget) -> f32 {
+    target.width() as f32 - config::CONFIG.get().display.margins.right - SCROLLBAR_WIDTH - 2.0
+}
+
+// right-aligned text on the given rows, clear of the scrollbar
+fn render_details(details: Vec<(usize, String)>, target: &mut raqote::DrawTarget) {
+    let right = details_right(target);
+
+    font::with(|font| {
+        for (row, text) in details {
+            let x = right - font.width(&text);
+            font.draw(target, &text, raqote::Point::new(x, line_y(row)));
+        }
+    });
+}
+
+// how many lines of text fit on the screen
+fn visible_lines(target: &raqote::DrawTarget) -> usize {
+    let display = &config::CONFIG.get().display;
+    let height = target.height() as f32 - display.margins.top - display.margins.bottom;
+
+    // the last line doesn't need spacing below it
+    ((height + display.line_spacing) / line_height()).floor().max(1.0) as usize
+}
+
+// a thumb along the right edge showing which `visible` of `total` lines are
+// on screen, starting from `top`
+fn render_scrollbar(target: &mut raqote::DrawTarget, top: usize, visible: usize, total: usize) {
+    let draw_options = raqote::DrawOptions::new();
+    let height = target.height() as f32;
+    let x = target.width() as f32 - SCROLLBAR_WIDTH;
+
+    let thumb_height = (height * visible as f32 / total as f32).max(2.0);
+    let y = (height - thumb_height) * top as f32 / (total - visible) as f32;
+
+    let mut pb = raqote::PathBuilder::new();
+    pb.rect(x, y, SCROLLBAR_WIDTH, thumb_height);
+    target.fill(&pb.finish(), &FOREGROUND_SOURCE, &draw_options);
+}
+
+// an exclamation mark in the top right corner
+fn render_warning(target: &mut raqote::DrawTarget) {
+    let draw_options = raqote::DrawOptions::new();
+    // left of the scrollbar, so the two don't overlap
+    let x = target.width() as f32 - SCROLLBAR_WIDTH - 4.0;
+
+    let mut pb = raqote::PathBuilder::new();
+    pb.rect(x, 1.0, 2.0, 8.0);
+    pb.rect(x, 11.0, 2.0, 2.0);
+    target.fill(&pb.finish(), &FOREGROUND_SOURCE, &draw_options);
 }
 
-fn build_param(param_config: &config::Param) -> Param {
-    Param::new(
+fn build_param(param_config: &config::Param, messages: &template::Shared) -> crate::param::Shared {
+    let template = param_config.osc.as_ref().map(|message| {
+        Template::parse(message, template::PARAM_PLACEHOLDERS).unwrap()
+    });
+
+    let conf = &*config::CONFIG.get();
+    let targets = param_config.targets.as_ref().map(|names| conf.osc.resolve(Some(names)));
+
+    crate::param::Param::new(
         &param_config.name,
-        param_config.value,
-        param_config.step,
-        param_config.min,
-        param_config.max,
-    )
-}
-
-fn build_patch(ui: &mut UI, patch_config: &config::Patch) -> Patch {
-    let mut items = patch_config.params.iter().map(|param_config| {
-        let param = build_param(&param_config);
-        let param_id = ui.register(param);
-        MenuItem::new(&param_config.name, Action::Push(param_id))
-    }).collect::<Vec<MenuItem>>();
+        param_config.kind(),
+        param_config.value(),
+        param_config.step(),
+        param_config.min(),
+        param_config.max(),
+        param_config.unit.clone(),
+        messages.clone(),
+        template,
+        targets,
+    ).shared()
+}
+
+fn build_pattern(
+    patch_config: &config::Patch,
+    sequencer_config: &config::Sequencer,
+    params: &Vec<crate::param::Shared>,
+    preset: &Preset,
+) -> Pattern {
+    let steps = sequencer_config.steps;
+    let param_configs = patch_config.all_params();
+
+    let mut tracks = sequencer_config.params.iter().filter_map(|name| {
+        let index = param_configs.iter().position(|p| &p.name == name);
+
+        match index {
+            Some(index) => {
+                let p = param_configs[index];
+                let mut track = Track::new(&p.name, TrackKind::Param, steps, p.value(), p.step(), p.min(), p.max());
+                track.param = Some(params[index].clone());
+                Some(track)
+            },
+            None => {
+                println!("sequencer param {} not found in patch {}", name, patch_config.name);
+                None
+            },
+        }
+    }).collect::<Vec<Track>>();
+
+    if sequencer_config.notes {
+        tracks.push(Track::new("note", TrackKind::Note, steps, 60.0, 1.0, 0.0, 127.0));
+    }
+
+    let mut pattern = Pattern::new(sequencer_config.bpm, steps, tracks);
+
+    if let Some(saved) = &preset.pattern {
+        pattern.merge(saved);
+    }
+
+    pattern
+}
+
+// what items inside a patch need from it
+struct PatchContext<'a> {
+    // param screens and params by name
+    params: HashMap<String, (ScreenId, crate::param::Shared)>,
+    targets: Option<&'a Vec<String>>,
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_string() } else { format!("{}/{}", path, name) }
+}
+
+fn build_screen(ui: &mut UI, screen: config::BuiltIn) -> ScreenId {
+    match screen {
+        config::BuiltIn::Status => ui.register(Status::new()),
+    }
+}
+
+fn build_items(ui: &mut UI, path: &str, items: &Vec<config::Item>, context: Option<&PatchContext>) -> Vec<MenuItem> {
+    items.iter().filter_map(|item| {
+        match item {
+            config::Item::Menu(menu_config) => {
+                let path = join(path, &menu_config.name);
+                let menu = build_menu(ui, &path, menu_config, context);
+                let menu_id = ui.register(menu);
+                ui.paths.insert(path, menu_id);
+                Some(MenuItem::new(&menu_config.name, Action::Push(menu_id)).detail(Detail::Submenu))
+            },
+            config::Item::Patch(patch_config) => {
+                Some(build_patch_item(ui, path, patch_config))
+            },
+            config::Item::Param(param_config) => {
+                let (param_id, param) = context?.params.get(&param_config.name)?;
+                let detail = Detail::Value(param.clone());
+                Some(MenuItem::new(&param_config.name, Action::Push(*param_id)).detail(detail))
+            },
+            config::Item::Action(action_config) => {
+                let trigger = Trigger::new(action_config, context.and_then(|c| c.targets));
+                Some(MenuItem::trigger(&action_config.name, trigger))
+            },
+            config::Item::Screen(screen_config) => {
+                let screen_id = build_screen(ui, screen_config.screen);
+                Some(MenuItem::new(&screen_config.name, Action::Push(screen_id)))
+            },
+        }
+    }).collect()
+}
+
+fn build_patch(ui: &mut UI, path: &str, patch_config: &config::Patch) -> Patch {
+    let preset = preset::load(path).unwrap_or_default();
+    let messages = Messages::new(path, patch_config).shared();
+    let param_configs = patch_config.all_params();
+    let running = Arc::new(AtomicBool::new(false));
+
+    let params = param_configs.iter().map(|param_config| {
+        let param = build_param(param_config, &messages);
+
+        if let Some(value) = preset.params.get(&param_config.name) {
+            let mut param = param.lock().unwrap();
+            param.value = param.clamp(*value);
+        }
+
+        param
+    }).collect::<Vec<crate::param::Shared>>();
+
+    let sequencer = patch_config.sequencer.as_ref().map(|sequencer_config| {
+        let pattern = build_pattern(patch_config, sequencer_config, &params, &preset);
+        crate::sequencer::Sequencer::new(messages.clone(), pattern)
+    });
+
+    let mut modulators = vec![];
+    let mut automations = vec![];
+    let mut context = PatchContext {
+        params: HashMap::new(),
+        targets: patch_config.targets.as_ref(),
+    };
+
+    for (param_config, param) in param_configs.iter().zip(params.iter().cloned()) {
+        if let Some(lfo_config) = &param_config.lfo {
+            let lfo = Lfo::new(lfo_config.shape, lfo_config.rate, lfo_config.depth);
+            modulators.push(Modulator::new(param.clone(), lfo));
+        }
+
+        let sync = param_config.automation.as_ref().map_or(false, |a| a.sync);
+        let tempo = match &sequencer {
+            Some(sequencer) if sync => Some(sequencer.pattern()),
+            None if sync => {
+                println!("param {} can't sync automation without a sequencer", param_config.name);
+                None
+            },
+            _ => None,
+        };
+
+        let recording = preset.automations.get(&param_config.name).cloned();
+        let automation = Automation::new(path, param.clone(), tempo, recording).shared();
+        automations.push(automation.clone());
+
+        ui.params.insert(join(path, &param_config.name), param.clone());
+
+        let param_id = ui.register(Param::new(param.clone(), automation));
+        context.params.insert(param_config.name.clone(), (param_id, param));
+    }
+
+    let mut items = build_items(ui, path, &patch_config.items, Some(&context));
+
+    if let Some(sequencer) = &sequencer {
+        let screen = Sequencer::new(path, sequencer.pattern());
+        let screen_id = ui.register(screen);
+        items.push(MenuItem::new("seq", Action::Push(screen_id)));
+    }
 
     items.push(MenuItem::new("<-", Action::Pop));
 
-    Patch::new(&patch_config.name, Menu::new(items))
+    let names = param_configs.iter().map(|p| p.name.clone());
+    ui.patches.insert(path.to_string(), PatchState {
+        params: params.clone(),
+        running: running.clone(),
+        pattern: sequencer.as_ref().map(|sequencer| sequencer.pattern()),
+        automations: names.zip(automations.iter().cloned()).collect(),
+    });
+
+    let conf = &*config::CONFIG.get();
+    let modulation = Modulation::new(modulators, conf.osc.control_rate);
+
+    Patch::new(path, messages, params, Menu::new(items), sequencer, modulation, automations, running)
 }
 
-fn build_menu(ui: &mut UI, menu_config: &config::Menu) -> Menu {
-    let mut items = menu_config.patches.iter().map(|patch_config| {
-        let patch = build_patch(ui, &patch_config);
-        let patch_id = ui.register(patch);
-        MenuItem::new(&patch_config.name, Action::Push(patch_id))
-    }).collect::<Vec<MenuItem>>();
+fn build_patch_item(ui: &mut UI, path: &str, patch_config: &config::Patch) -> MenuItem {
+    let path = join(path, &patch_config.name);
+    let patch = build_patch(ui, &path, patch_config);
+    let patch_id = ui.register(patch);
+    ui.paths.insert(path, patch_id);
+    MenuItem::new(&patch_config.name, Action::Push(patch_id))
+}
 
-    items.push(MenuItem::new("↩", Action::Pop));
+// `context` is set for menus inside a patch
+fn build_menu(ui: &mut UI, path: &str, menu_config: &config::Menu, context: Option<&PatchContext>) -> Menu {
+    let mut items = build_items(ui, path, &menu_config.items, context);
+    items.push(MenuItem::new("<-", Action::Pop));
 
     Menu::new(items)
 }
 
+// characters none of the fonts have are drawn as a replacement glyph. This
+// runs once the config is loaded, before the UI thread has its own font.
+pub fn check_labels() {
+    let conf = &*config::CONFIG.get();
+    let font = font::Font::configured();
+
+    for label in config::labels(conf) {
+        let missing = font.missing(label);
+        if !missing.is_empty() {
+            let chars = missing.iter().collect::<String>();
+            println!("label {} has characters the font can't show: {}", label, chars);
+        }
+    }
+}
+
 pub fn build_ui(menus: &Vec<config::Menu>) -> UI {
     let mut ui = UI::new();
 
-    let items = menus.iter().map(|menu| {
-        let x = build_menu(&mut ui, &menu);
-        let menu_id = ui.register(x);
-        MenuItem::new(&menu.name, Action::Push(menu_id))
+    let mut items = menus.iter().map(|menu_config| {
+        let menu = build_menu(&mut ui, &menu_config.name, menu_config, None);
+        let menu_id = ui.register(menu);
+        ui.paths.insert(menu_config.name.clone(), menu_id);
+        MenuItem::new(&menu_config.name, Action::Push(menu_id)).detail(Detail::Submenu)
     }).collect::<Vec<MenuItem>>();
 
+    let conf = &*config::CONFIG.get();
+    if conf.server.is_some() {
+        let status_id = build_screen(&mut ui, config::BuiltIn::Status);
+        items.push(MenuItem::new("status", Action::Push(status_id)));
+    }
+
     let root_menu = ui.register(Menu::new(items));
     ui.push_screen(root_menu);
 
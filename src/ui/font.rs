@@ -0,0 +1,319 @@
+mod bdf;
+mod pcf;
+
+use crate::config;
+use euclid::default::{Point2D, Size2D};
+use font_kit::canvas::{Canvas, Format, RasterizationOptions};
+use font_kit::hinting::HintingOptions;
+use font_kit::loader::FontTransform;
+use raqote;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+static EMBEDDED: &'static [u8] = include_bytes!("fonts/inconsolata.ttf");
+
+// drawn in place of characters none of the fonts have
+const REPLACEMENT: char = '\u{FFFD}';
+
+thread_local! {
+    // only the UI thread renders, so the font and its glyphs are loaded there once
+    static FONT: Font = Font::configured();
+}
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "IO error: {}", error)]
+    IoError {
+        error: io::Error,
+    },
+    #[fail(display = "invalid font: {}", reason)]
+    FontError {
+        reason: String,
+    },
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::IoError { error: error }
+    }
+}
+
+fn font_error(reason: &str) -> Error {
+    Error::FontError { reason: reason.to_string() }
+}
+
+// a pre-rasterized glyph, `left` and `top` are the offset of its top left
+// corner from the pen position on the baseline
+#[derive(Debug)]
+pub struct Glyph {
+    pub width: usize,
+    pub height: usize,
+    pub left: i32,
+    pub top: i32,
+    pub advance: f32,
+    // one alpha value per pixel, row by row
+    pub coverage: Vec<u8>,
+}
+
+enum Face {
+    Outline {
+        font: font_kit::font::Font,
+        size: f32,
+    },
+    Bitmap {
+        glyphs: HashMap<char, Rc<Glyph>>,
+        ascent: f32,
+        descent: f32,
+    },
+}
+
+impl Face {
+    fn embedded(size: f32) -> Self {
+        let font = font_kit::font::Font::from_bytes(Arc::new(EMBEDDED.to_vec()), 0).unwrap();
+        Face::Outline { font: font, size: size }
+    }
+
+    // BDF and PCF files are bitmap fonts, anything else is loaded as an outline
+    // font at `size`
+    fn load(path: &Path, size: f32) -> Result<Self, Error> {
+        let (glyphs, ascent, descent) = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bdf") => bdf::parse(&fs::read_to_string(path)?)?,
+            Some("pcf") => pcf::parse(&fs::read(path)?)?,
+            _ => {
+                let bytes = Arc::new(fs::read(path)?);
+                let font = font_kit::font::Font::from_bytes(bytes, 0)
+                    .map_err(|_| font_error("couldn't load outline font"))?;
+                return Ok(Face::Outline { font: font, size: size });
+            },
+        };
+
+        Ok(Face::Bitmap { glyphs: glyphs, ascent: ascent, descent: descent })
+    }
+
+    fn metrics(&self) -> (f32, f32) {
+        match self {
+            Face::Outline { font, size } => {
+                let metrics = font.metrics();
+                let scale = size / metrics.units_per_em as f32;
+                (metrics.ascent * scale, -metrics.descent * scale)
+            },
+            Face::Bitmap { ascent, descent, .. } => (*ascent, *descent),
+        }
+    }
+
+    fn has_glyph(&self, c: char) -> bool {
+        match self {
+            Face::Outline { font, .. } => font.glyph_for_char(c).is_some(),
+            Face::Bitmap { glyphs, .. } => glyphs.contains_key(&c),
+        }
+    }
+
+    fn glyph(&self, c: char) -> Option<Rc<Glyph>> {
+        match self {
+            Face::Outline { font, size } => rasterize(font, *size, c).map(Rc::new),
+            Face::Bitmap { glyphs, .. } => glyphs.get(&c).cloned(),
+        }
+    }
+}
+
+// the configured font followed by its fallbacks, each glyph comes from the
+// first one that has it
+pub struct Font {
+    faces: Vec<Face>,
+    cache: RefCell<HashMap<char, Rc<Glyph>>>,
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+impl Font {
+    // the line metrics come from the first face
+    fn new(faces: Vec<Face>) -> Self {
+        let (ascent, descent) = faces[0].metrics();
+
+        Font {
+            faces: faces,
+            cache: RefCell::new(HashMap::new()),
+            ascent: ascent,
+            descent: descent,
+        }
+    }
+
+    // the built-in font is always the last fallback
+    pub fn configured() -> Self {
+        let conf = &*config::CONFIG.get();
+        let size = conf.display.size;
+
+        let paths = conf.display.font.iter().chain(conf.display.fallbacks.iter());
+        let mut faces = paths.filter_map(|path| {
+            Face::load(Path::new(path), size).map_err(|err| {
+                println!("error loading font {}: {}", path, err);
+            }).ok()
+        }).collect::<Vec<_>>();
+
+        faces.push(Face::embedded(size));
+        Font::new(faces)
+    }
+
+    fn has_glyph(&self, c: char) -> bool {
+        self.faces.iter().any(|face| face.has_glyph(c))
+    }
+
+    // the characters in `text` that none of the faces have
+    pub fn missing(&self, text: &str) -> Vec<char> {
+        let mut missing = text.chars().filter(|c| !self.has_glyph(*c)).collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    fn find(&self, c: char) -> Option<Rc<Glyph>> {
+        self.faces.iter().filter_map(|face| face.glyph(c)).next()
+    }
+
+    // the replacement character if any face has it, otherwise an empty box
+    fn replacement(&self) -> Rc<Glyph> {
+        self.find(REPLACEMENT).unwrap_or_else(|| {
+            let height = self.ascent.round().max(3.0) as usize;
+            let width = (height / 2).max(3);
+
+            let coverage = (0..height).flat_map(|y| {
+                (0..width).map(move |x| {
+                    if y == 0 || y == height - 1 || x == 0 || x == width - 1 { 255 } else { 0 }
+                })
+            }).collect();
+
+            Rc::new(Glyph {
+                width: width,
+                height: height,
+                left: 1,
+                top: -(height as i32),
+                advance: (width + 2) as f32,
+                coverage: coverage,
+            })
+        })
+    }
+
+    pub fn glyph(&self, c: char) -> Rc<Glyph> {
+        if let Some(glyph) = self.cache.borrow().get(&c) {
+            return glyph.clone();
+        }
+
+        let glyph = self.find(c).unwrap_or_else(|| self.replacement());
+        self.cache.borrow_mut().insert(c, glyph.clone());
+        glyph
+    }
+
+    pub fn width(&self, text: &str) -> f32 {
+        text.chars().map(|c| self.glyph(c).advance).sum()
+    }
+
+    // draw white text with its baseline starting at `start`
+    pub fn draw(&self, target: &mut raqote::DrawTarget, text: &str, start: raqote::Point) {
+        let right = target.width() as f32;
+        self.draw_clipped(target, text, start, 0.0, right);
+    }
+
+    // like `draw` but only the pixels between `left` and `right`
+    pub fn draw_clipped(&self, target: &mut raqote::DrawTarget, text: &str, start: raqote::Point, left: f32, right: f32) {
+        let clip = (left.round() as i32, right.round() as i32);
+        let mut x = start.x;
+
+        for c in text.chars() {
+            let glyph = self.glyph(c);
+            blit(target, &glyph, x.round() as i32 + glyph.left, start.y.round() as i32 + glyph.top, clip);
+            x += glyph.advance;
+        }
+    }
+}
+
+fn rasterize(font: &font_kit::font::Font, size: f32, c: char) -> Option<Glyph> {
+    let id = font.glyph_for_char(c)?;
+    let transform = FontTransform::identity();
+    let hinting = HintingOptions::None;
+    let options = RasterizationOptions::GrayscaleAa;
+
+    let bounds = font.raster_bounds(id, size, &transform, &Point2D::zero(), hinting, options).ok()?;
+    let advance = font.advance(id).ok()?.x * size / font.metrics().units_per_em as f32;
+
+    let width = bounds.size.width.max(0) as usize;
+    let height = bounds.size.height.max(0) as usize;
+
+    // nothing to rasterize for spaces
+    if width == 0 || height == 0 {
+        return Some(Glyph {
+            width: 0,
+            height: 0,
+            left: 0,
+            top: 0,
+            advance: advance,
+            coverage: vec![],
+        });
+    }
+
+    let mut canvas = Canvas::new(&Size2D::new(width as u32, height as u32), Format::A8);
+
+    // the bounds are y-up from the baseline, so the baseline goes `top` pixels
+    // down the canvas to put the glyph in its top left corner
+    let top = bounds.origin.y + bounds.size.height;
+    let origin = Point2D::new(-bounds.origin.x as f32, top as f32);
+    font.rasterize_glyph(&mut canvas, id, size, &transform, &origin, hinting, options).ok()?;
+
+    let coverage = (0..height).flat_map(|y| {
+        canvas.pixels[y * canvas.stride..y * canvas.stride + width].to_vec()
+    }).collect();
+
+    Some(Glyph {
+        width: width,
+        height: height,
+        left: bounds.origin.x,
+        top: -top,
+        advance: advance,
+        coverage: coverage,
+    })
+}
+
+// composite the glyph's coverage over the target as white, within the columns
+// `clip` spans
+fn blit(target: &mut raqote::DrawTarget, glyph: &Glyph, left: i32, top: i32, clip: (i32, i32)) {
+    let width = target.width();
+    let height = target.height();
+    let (clip_left, clip_right) = (clip.0.max(0), clip.1.min(width));
+    let data = target.get_data_mut();
+
+    for row in 0..glyph.height {
+        let y = top + row as i32;
+        if y < 0 || y >= height {
+            continue;
+        }
+
+        for col in 0..glyph.width {
+            let x = left + col as i32;
+            if x < clip_left || x >= clip_right {
+                continue;
+            }
+
+            let alpha = glyph.coverage[row * glyph.width + col] as u32;
+            if alpha == 0 {
+                continue;
+            }
+
+            // premultiplied ARGB, so white at `alpha` is `alpha` in every channel
+            let index = (y * width + x) as usize;
+            let pixel = data[index];
+            let blend = |shift: u32| {
+                let dst = (pixel >> shift) & 0xFF;
+                (alpha + dst * (255 - alpha) / 255) << shift
+            };
+            data[index] = blend(24) | blend(16) | blend(8) | blend(0);
+        }
+    }
+}
+
+pub fn with<T, F: FnOnce(&Font) -> T>(f: F) -> T {
+    FONT.with(f)
+}
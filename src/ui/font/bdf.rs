@@ -0,0 +1,176 @@
+// reads the glyphs out of X11 BDF bitmap font files
+use crate::ui::font::{font_error, Error, Glyph};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+fn numbers(args: &[&str]) -> Result<Vec<i32>, Error> {
+    args.iter()
+        .map(|arg| arg.parse::<i32>().map_err(|_| font_error(&format!("invalid number {}", arg))))
+        .collect()
+}
+
+struct Char {
+    encoding: Option<char>,
+    advance: f32,
+    bbx: (usize, usize, i32, i32),
+    rows: Vec<Vec<u8>>,
+}
+
+impl Char {
+    fn glyph(&self) -> Glyph {
+        let (width, height, xoff, yoff) = self.bbx;
+        let mut coverage = vec![0u8; width * height];
+
+        // each row is hex encoded, most significant bit first
+        for (y, row) in self.rows.iter().take(height).enumerate() {
+            for x in 0..width {
+                let byte = row.get(x / 8).cloned().unwrap_or(0);
+                if byte & (0x80 >> (x % 8)) != 0 {
+                    coverage[y * width + x] = 255;
+                }
+            }
+        }
+
+        Glyph {
+            width: width,
+            height: height,
+            left: xoff,
+            top: -(yoff + height as i32),
+            advance: self.advance,
+            coverage: coverage,
+        }
+    }
+}
+
+fn hex(line: &str) -> Result<Vec<u8>, Error> {
+    (0..line.len() / 2)
+        .map(|i| u8::from_str_radix(&line[i * 2..i * 2 + 2], 16).map_err(|_| font_error("invalid bitmap row")))
+        .collect()
+}
+
+pub fn parse(text: &str) -> Result<(HashMap<char, Rc<Glyph>>, f32, f32), Error> {
+    let mut glyphs = HashMap::new();
+    let mut ascent = None;
+    let mut descent = None;
+    let mut current: Option<Char> = None;
+    let mut in_bitmap = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (keyword, args) = match words.split_first() {
+            Some((keyword, args)) => (*keyword, args),
+            None => continue,
+        };
+
+        if in_bitmap {
+            let c = current.as_mut().ok_or_else(|| font_error("BITMAP outside of a char"))?;
+
+            if keyword == "ENDCHAR" {
+                in_bitmap = false;
+                if let Some(encoding) = c.encoding {
+                    glyphs.insert(encoding, Rc::new(c.glyph()));
+                }
+                current = None;
+            } else {
+                c.rows.push(hex(line)?);
+            }
+            continue;
+        }
+
+        match keyword {
+            "FONT_ASCENT" => ascent = numbers(args)?.first().cloned(),
+            "FONT_DESCENT" => descent = numbers(args)?.first().cloned(),
+            "STARTCHAR" => {
+                current = Some(Char {
+                    encoding: None,
+                    advance: 0.0,
+                    bbx: (0, 0, 0, 0),
+                    rows: vec![],
+                });
+            },
+            "ENCODING" | "DWIDTH" | "BBX" | "BITMAP" => {
+                let c = current.as_mut().ok_or_else(|| font_error(&format!("{} outside of a char", keyword)))?;
+                let values = numbers(args)?;
+
+                match (keyword, values.as_slice()) {
+                    // -1 means the glyph has no standard encoding
+                    ("ENCODING", [code, ..]) => c.encoding = std::char::from_u32(*code as u32).filter(|_| *code >= 0),
+                    ("DWIDTH", [x, ..]) => c.advance = *x as f32,
+                    ("BBX", [w, h, x, y]) if *w >= 0 && *h >= 0 => c.bbx = (*w as usize, *h as usize, *x, *y),
+                    ("BITMAP", []) => in_bitmap = true,
+                    _ => return Err(font_error(&format!("invalid {}", keyword))),
+                }
+            },
+            _ => {},
+        }
+    }
+
+    match (ascent, descent) {
+        (Some(ascent), Some(descent)) => Ok((glyphs, ascent as f32, descent as f32)),
+        _ => Err(font_error("missing FONT_ASCENT or FONT_DESCENT")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FONT: &str = "\
+STARTFONT 2.1
+FONT -test-fixed-medium-r-normal--8-80-75-75-c-40-iso10646-1
+SIZE 8 75 75
+FONTBOUNDINGBOX 4 8 0 -1
+STARTPROPERTIES 2
+FONT_ASCENT 7
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR A
+ENCODING 65
+SWIDTH 500 0
+DWIDTH 5 0
+BBX 3 4 1 -1
+BITMAP
+40
+A0
+E0
+A0
+ENDCHAR
+STARTCHAR unencoded
+ENCODING -1
+DWIDTH 5 0
+BBX 1 1 0 0
+BITMAP
+80
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn parses_glyphs() {
+        let (glyphs, ascent, descent) = parse(FONT).unwrap();
+        assert_eq!((ascent, descent), (7.0, 1.0));
+
+        // the -1 encoded glyph is skipped
+        assert_eq!(glyphs.len(), 1);
+
+        let glyph = &glyphs[&'A'];
+        assert_eq!((glyph.width, glyph.height, glyph.left, glyph.top), (3, 4, 1, -3));
+        assert_eq!(glyph.advance, 5.0);
+        assert_eq!(glyph.coverage, vec![
+            0, 255, 0,
+            255, 0, 255,
+            255, 255, 255,
+            255, 0, 255,
+        ]);
+    }
+
+    #[test]
+    fn rejects_bad_fonts() {
+        assert!(parse("STARTFONT 2.1\nENDFONT\n").is_err());
+        assert!(parse("FONT_ASCENT 7\nFONT_DESCENT 1\nENCODING 65\n").is_err());
+        assert!(parse(&FONT.replace("BBX 3 4 1 -1", "BBX 3")).is_err());
+        assert!(parse(&FONT.replace("E0", "zz")).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+// reads the glyphs out of (uncompressed) X11 PCF bitmap font files
+use crate::ui::font::{font_error, Error, Glyph};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const ACCELERATORS: u32 = 1 << 1;
+const METRICS: u32 = 1 << 2;
+const BITMAPS: u32 = 1 << 3;
+const BDF_ENCODINGS: u32 = 1 << 5;
+const BDF_ACCELERATORS: u32 = 1 << 8;
+
+const COMPRESSED_METRICS: u32 = 0x100;
+const BYTE_MASK: u32 = 1 << 2;
+const BIT_MASK: u32 = 1 << 3;
+
+struct Table {
+    kind: u32,
+    offset: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Metrics {
+    left: i32,
+    right: i32,
+    width: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+// reads a table's values, every table starts with its format in little endian
+// and the rest is in the byte order that format says
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], pos: usize) -> Self {
+        Reader { bytes: bytes, pos: pos, big_endian: false }
+    }
+
+    fn table(bytes: &'a [u8], table: &Table) -> Result<(Self, u32), Error> {
+        let mut reader = Reader::new(bytes, table.offset);
+        let format = reader.u32()?;
+        reader.big_endian = format & BYTE_MASK != 0;
+        Ok((reader, format))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.bytes.get(self.pos..self.pos + len)
+            .ok_or_else(|| font_error("unexpected end of file"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.take(2)?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.take(4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.take(len).map(|_| ())
+    }
+}
+
+fn tables(bytes: &[u8]) -> Result<Vec<Table>, Error> {
+    let mut reader = Reader::new(bytes, 0);
+
+    if reader.take(4)? != b"\x01fcp" {
+        return Err(font_error("missing PCF header"));
+    }
+
+    let count = reader.u32()?;
+    (0..count).map(|_| {
+        // the format and size are repeated in the table itself
+        let kind = reader.u32()?;
+        reader.skip(8)?;
+        let offset = reader.u32()? as usize;
+
+        Ok(Table { kind: kind, offset: offset })
+    }).collect()
+}
+
+fn metrics(bytes: &[u8], table: &Table) -> Result<Vec<Metrics>, Error> {
+    let (mut reader, format) = Reader::table(bytes, table)?;
+
+    if format & COMPRESSED_METRICS != 0 {
+        let count = reader.u16()?;
+        (0..count).map(|_| {
+            let mut value = || reader.u8().map(|byte| byte as i32 - 0x80);
+            let metrics = Metrics {
+                left: value()?,
+                right: value()?,
+                width: value()?,
+                ascent: value()?,
+                descent: value()?,
+            };
+            Ok(metrics)
+        }).collect()
+    } else {
+        let count = reader.u32()?;
+        (0..count).map(|_| {
+            let mut value = || reader.u16().map(|value| value as i16 as i32);
+            let metrics = Metrics {
+                left: value()?,
+                right: value()?,
+                width: value()?,
+                ascent: value()?,
+                descent: value()?,
+            };
+            // attributes
+            value()?;
+            Ok(metrics)
+        }).collect()
+    }
+}
+
+fn glyphs(bytes: &[u8], table: &Table, metrics: &[Metrics]) -> Result<Vec<Glyph>, Error> {
+    let (mut reader, format) = Reader::table(bytes, table)?;
+
+    let count = reader.u32()? as usize;
+    if count != metrics.len() {
+        return Err(font_error("bitmap and metrics counts differ"));
+    }
+
+    let offsets = (0..count).map(|_| reader.u32().map(|offset| offset as usize)).collect::<Result<Vec<_>, _>>()?;
+    let sizes = (0..4).map(|_| reader.u32()).collect::<Result<Vec<_>, _>>()?;
+    let data = reader.take(sizes[(format & 3) as usize] as usize)?;
+
+    // rows are padded to `pad` bytes and stored in `unit` byte units
+    let pad = 1 << (format & 3);
+    let unit = 1 << ((format >> 4) & 3);
+    let msb_first = format & BIT_MASK != 0;
+    let big_endian = format & BYTE_MASK != 0;
+
+    metrics.iter().zip(offsets).map(|(m, offset)| {
+        let width = (m.right - m.left).max(0) as usize;
+        let height = (m.ascent + m.descent).max(0) as usize;
+        let stride = (width + pad * 8 - 1) / (pad * 8) * pad;
+        let mut coverage = vec![0u8; width * height];
+
+        for y in 0..height {
+            for x in 0..width {
+                // swap the bytes within a unit if they're stored least significant first
+                let mut index = x / 8;
+                if !big_endian && unit > 1 {
+                    index = index / unit * unit + unit - 1 - index % unit;
+                }
+
+                let byte = *data.get(offset + y * stride + index)
+                    .ok_or_else(|| font_error("bitmap out of range"))?;
+                let bit = if msb_first { 0x80 >> (x % 8) } else { 1 << (x % 8) };
+
+                if byte & bit != 0 {
+                    coverage[y * width + x] = 255;
+                }
+            }
+        }
+
+        Ok(Glyph {
+            width: width,
+            height: height,
+            left: m.left,
+            top: -m.ascent,
+            advance: m.width as f32,
+            coverage: coverage,
+        })
+    }).collect()
+}
+
+fn encodings(bytes: &[u8], table: &Table) -> Result<Vec<(char, usize)>, Error> {
+    let (mut reader, _) = Reader::table(bytes, table)?;
+
+    let min_byte2 = reader.u16()? as u32;
+    let max_byte2 = reader.u16()? as u32;
+    let min_byte1 = reader.u16()? as u32;
+    let max_byte1 = reader.u16()? as u32;
+    // default char
+    reader.u16()?;
+
+    let mut chars = vec![];
+    for byte1 in min_byte1..=max_byte1 {
+        for byte2 in min_byte2..=max_byte2 {
+            let index = reader.u16()?;
+            if index == 0xFFFF {
+                continue;
+            }
+
+            if let Some(c) = std::char::from_u32(byte1 << 8 | byte2) {
+                chars.push((c, index as usize));
+            }
+        }
+    }
+
+    Ok(chars)
+}
+
+fn font_metrics(bytes: &[u8], table: &Table) -> Result<(f32, f32), Error> {
+    let (mut reader, _) = Reader::table(bytes, table)?;
+
+    // flags, overlap and padding
+    reader.skip(8)?;
+    let ascent = reader.u32()? as i32;
+    let descent = reader.u32()? as i32;
+
+    Ok((ascent as f32, descent as f32))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<(HashMap<char, Rc<Glyph>>, f32, f32), Error> {
+    let tables = tables(bytes)?;
+    let find = |kind: u32| tables.iter().find(|table| table.kind == kind);
+    let missing = |name: &str| font_error(&format!("missing {} table", name));
+
+    let metrics = metrics(bytes, find(METRICS).ok_or_else(|| missing("metrics"))?)?;
+    let glyphs = glyphs(bytes, find(BITMAPS).ok_or_else(|| missing("bitmaps"))?, &metrics)?
+        .into_iter()
+        .map(Rc::new)
+        .collect::<Vec<_>>();
+    let encodings = encodings(bytes, find(BDF_ENCODINGS).ok_or_else(|| missing("encodings"))?)?;
+
+    let accelerators = find(BDF_ACCELERATORS).or_else(|| find(ACCELERATORS)).ok_or_else(|| missing("accelerators"))?;
+    let (ascent, descent) = font_metrics(bytes, accelerators)?;
+
+    let chars = encodings.into_iter()
+        .filter_map(|(c, index)| glyphs.get(index).map(|glyph| (c, glyph.clone())))
+        .collect();
+
+    Ok((chars, ascent, descent))
+}
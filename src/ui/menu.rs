@@ -1,10 +1,36 @@
+use crate::config;
+use crate::param;
+use crate::trigger::Trigger;
 use crate::ui;
 use raqote;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+// space between a label and its detail
+const DETAIL_GAP: f32 = 4.0;
+
+// shown right-aligned next to an item's label, read again on every render
+#[derive(Debug, Clone)]
+pub enum Detail {
+    Value(param::Shared),
+    Submenu,
+}
+
+impl Detail {
+    fn text(&self) -> Option<String> {
+        match self {
+            Detail::Value(param) => Some(param.lock().unwrap().display()),
+            Detail::Submenu => Some("›".to_string()),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct MenuItem {
     label: String,
     action: Option<ui::Action>,
+    trigger: Option<Trigger>,
+    detail: Option<Detail>,
 }
 
 impl MenuItem {
@@ -12,8 +38,24 @@ impl MenuItem {
         MenuItem {
             label: label.to_string(),
             action: Some(action),
+            trigger: None,
+            detail: None,
         }
     }
+
+    pub fn trigger(label: &str, trigger: Trigger) -> Self {
+        MenuItem {
+            label: label.to_string(),
+            action: None,
+            trigger: Some(trigger),
+            detail: None,
+        }
+    }
+
+    pub fn detail(mut self, detail: Detail) -> Self {
+        self.detail = Some(detail);
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -21,46 +63,129 @@ pub struct Menu {
     path: Vec<usize>,
     selected: usize,
     items: Vec<MenuItem>,
+    // the first item on screen, moved when rendering to keep `selected` visible
+    top: Cell<usize>,
+    wrap: bool,
+    // when the selection last changed, which restarts the marquee
+    selected_at: Instant,
+    // whether the selected label is too wide and needs the marquee
+    overflows: Cell<bool>,
+    // the momentary trigger the button went down on, released even if the
+    // selection moved while it was held
+    held: Option<Trigger>,
 }
 
 impl Menu {
     pub fn new(menu_items: Vec<MenuItem>) -> Self {
+        let conf = &*config::CONFIG.get();
+
         Menu {
             path: vec![],
             selected: 0,
-            items: menu_items
+            items: menu_items,
+            top: Cell::new(0),
+            wrap: conf.display.wrap,
+            selected_at: Instant::now(),
+            overflows: Cell::new(false),
+            held: None,
         }
     }
 
     pub fn down(&mut self) {
         if self.selected < (self.items.len() - 1) {
             self.selected += 1;
+        } else if self.wrap {
+            self.selected = 0;
         }
+        self.selected_at = Instant::now();
     }
 
     pub fn up(&mut self) {
         if self.selected > 0 {
             self.selected -= 1;
+        } else if self.wrap {
+            self.selected = self.items.len() - 1;
         }
+        self.selected_at = Instant::now();
     }
 
     pub fn select(&mut self) -> Option<ui::Action> {
         let item = &self.items[self.selected];
+
+        if let Some(trigger) = &item.trigger {
+            if !trigger.is_momentary() {
+                trigger.fire();
+            }
+        }
+
         item.action
     }
+
+    fn momentary(&self) -> Option<&Trigger> {
+        let trigger = self.items[self.selected].trigger.as_ref()?;
+        if trigger.is_momentary() { Some(trigger) } else { None }
+    }
 }
 
 impl ui::Screen for Menu {
     fn render(&self, target: &mut raqote::DrawTarget) {
-        let lines = self.items.iter().enumerate().map(|(i, item)| {
-            if self.selected == i {
-                format!("> {}", item.label)
-            } else {
-                format!("  {}", item.label)
+        let visible = ui::visible_lines(target);
+
+        let mut top = self.top.get();
+        if self.selected < top {
+            top = self.selected;
+        } else if self.selected >= top + visible {
+            top = self.selected + 1 - visible;
+        }
+        self.top.set(top);
+
+        let details = self.items.iter().skip(top).take(visible).enumerate().filter_map(|(row, item)| {
+            Some((row, item.detail.as_ref()?.text()?))
+        }).collect::<Vec<_>>();
+
+        target.clear(ui::BACKGROUND);
+
+        let left = config::CONFIG.get().display.margins.left;
+        let right = ui::details_right(target);
+
+        // labels stop short of their details, the selected one scrolls if it
+        // doesn't fit and the rest are truncated
+        ui::font::with(|font| {
+            for (row, (i, item)) in self.items.iter().enumerate().skip(top).take(visible).enumerate() {
+                let marker = if self.selected == i { "> " } else { "  " };
+                font.draw(target, marker, raqote::Point::new(left, ui::line_y(row)));
+
+                let x = left + font.width(marker);
+                let detail_width = details.iter().find(|(r, _)| *r == row)
+                    .map_or(0.0, |(_, text)| font.width(text) + DETAIL_GAP);
+
+                // a wide detail can leave no room for the label at all
+                let width = (right - detail_width - x).max(0.0);
+                if width == 0.0 {
+                    if self.selected == i {
+                        self.overflows.set(false);
+                    }
+                    continue;
+                }
+
+                let scrolling = if self.selected == i { Some(self.selected_at.elapsed()) } else { None };
+                let overflows = ui::render_label(target, font, &item.label, x, row, width, scrolling);
+
+                if self.selected == i {
+                    self.overflows.set(overflows);
+                }
             }
-        }).collect();
+        });
+
+        ui::render_details(details, target);
+
+        if self.items.len() > visible {
+            ui::render_scrollbar(target, top, visible, self.items.len());
+        }
+    }
 
-        ui::render_lines(lines, target);
+    fn next_tick(&self) -> Option<Duration> {
+        if self.overflows.get() { Some(ui::MARQUEE_FRAME) } else { None }
     }
 
     fn handle(&mut self, input: ui::Input) -> Option<ui::Action> {
@@ -76,6 +201,24 @@ impl ui::Screen for Menu {
             ui::Input::Press => {
                 self.select()
             },
+            ui::Input::LongPress => None,
+            ui::Input::Down => {
+                if let Some(trigger) = self.held.take() {
+                    trigger.up();
+                }
+
+                self.held = self.momentary().cloned();
+                if let Some(trigger) = &self.held {
+                    trigger.down();
+                }
+                None
+            },
+            ui::Input::Release => {
+                if let Some(trigger) = self.held.take() {
+                    trigger.up();
+                }
+                None
+            },
         }
     }
 }
@@ -1,86 +1,136 @@
-use crate::osc;
+use crate::automation;
+use crate::param;
 use crate::ui;
 use raqote;
+use std::cell::Cell;
+use std::time::Duration;
+
+// how often to check for changes from LFOs and automation
+const REFRESH: Duration = Duration::from_millis(50);
+
+// the value, modulated value, and whether automation is recording and playing
+type Shown = (f32, Option<f32>, bool, bool);
 
 #[derive(Debug)]
 pub struct Param {
-    pub name: String,
-    pub value: f32,
-    pub step: f32,
-    pub min: f32,
-    pub max: f32,
+    param: param::Shared,
+    automation: automation::Shared,
+    // a press only goes back once it's released without turning into a
+    // long press
+    pressed: bool,
+    // what was last rendered
+    shown: Cell<Option<Shown>>,
 }
 
 impl Param {
-    pub fn new(name: &str, value: f32, step: f32, min: f32, max: f32) -> Self {
+    pub fn new(param: param::Shared, automation: automation::Shared) -> Self {
         Param {
-            name: name.to_string(),
-            value: value,
-            step: step,
-            min: min,
-            max: max,
+            param: param,
+            automation: automation,
+            pressed: false,
+            shown: Cell::new(None),
         }
     }
 
-    pub fn inc(&mut self) {
-        self.value += self.step;
-        if self.value > self.max {
-            self.value = self.max;
-        }
-    }
-
-    pub fn dec(&mut self) {
-        self.value -= self.step;
-        if self.value < self.min {
-            self.value = self.min;
-        }
-    }
+    fn change(&mut self, inc: bool) {
+        let value = {
+            let mut param = self.param.lock().unwrap();
+            if inc { param.inc() } else { param.dec() }
+            param.send();
+            param.value
+        };
 
-    pub fn perc(&self) -> f32 {
-        self.value / self.max
+        self.automation.lock().unwrap().capture(value);
     }
 
-    pub fn send(&self) {
-        let res = osc::send("set", Some(vec![
-            osc::Type::String(self.name.clone()),
-            osc::Type::Float(self.value),
-        ]));
-
-        if let Err(err) = res {
-            println!("error sending OSC message: {}", err);
-        }
+    // locks the automation before the param, the same order it locks them in
+    fn state(&self) -> Shown {
+        let automation = self.automation.lock().unwrap();
+        let param = self.param.lock().unwrap();
+        (param.value, param.modulated, automation.is_recording(), automation.is_playing())
     }
 }
 
 impl ui::Screen for Param {
     fn render(&self, target: &mut raqote::DrawTarget) {
-        let mut lines = vec![
-            format!("{}:", self.name),
-            format!("{:.*}", 2, self.value),
-        ];
+        self.shown.set(Some(self.state()));
+
+        let automation = self.automation.lock().unwrap();
+        let param = self.param.lock().unwrap();
+
+        let status = if automation.is_recording() {
+            " REC"
+        } else if automation.is_playing() {
+            " PLAY"
+        } else {
+            ""
+        };
 
-        let max_width = 13.0; // max chars wide
-        let val_width = (max_width * self.perc()).round() as usize;
-        lines.push(vec!["-"; val_width].join(""));
+        let mut lines = vec![format!("{}:{}", param.name, status)];
+
+        match &param.kind {
+            param::Kind::Float | param::Kind::Int => {
+                lines.push(param.display());
+
+                let max_width = ui::font::with(|font| (ui::text_width(target) / font.width("-")).floor());
+                let val_width = (max_width * param.perc()).round() as usize;
+                lines.push(vec!["-"; val_width].join(""));
+            },
+            param::Kind::Toggle => {
+                let check = if param.value >= 0.5 { "x" } else { " " };
+                lines.push(format!("[{}] {}", check, param.display()));
+            },
+            param::Kind::Choice(choices) => {
+                lines.push(format!("< {} >", param.display()));
+                lines.push(format!("{}/{}", param.value as usize + 1, choices.len()));
+            },
+        }
+
+        if let Some(modulated) = param.modulated {
+            lines.push(format!("~{}", param.kind.display(modulated)));
+        }
 
         ui::render_lines(lines, target);
     }
 
+    fn next_tick(&self) -> Option<Duration> {
+        Some(REFRESH)
+    }
+
+    fn tick(&mut self) -> bool {
+        self.shown.get() != Some(self.state())
+    }
+
     fn handle(&mut self, input: ui::Input) -> Option<ui::Action> {
         match input {
             ui::Input::Left => {
-                self.dec();
-                self.send();
+                self.change(false);
                 None
             },
             ui::Input::Right => {
-                self.inc();
-                self.send();
+                self.change(true);
                 None
             },
             ui::Input::Press => {
-                Some(ui::Action::Pop)
+                self.pressed = true;
+                None
+            },
+            ui::Input::LongPress => {
+                if self.pressed {
+                    self.pressed = false;
+                    self.automation.lock().unwrap().toggle();
+                }
+                None
+            },
+            ui::Input::Release => {
+                if self.pressed {
+                    self.pressed = false;
+                    Some(ui::Action::Pop)
+                } else {
+                    None
+                }
             },
+            ui::Input::Down => None,
         }
     }
 }
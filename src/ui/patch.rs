@@ -1,35 +1,114 @@
+use crate::automation;
+use crate::config;
+use crate::modulation::Modulation;
 use crate::osc;
+use crate::param;
+use crate::preset;
+use crate::sequencer::Sequencer;
+use crate::template;
 use crate::ui;
 use raqote;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct Patch {
-    name: String,
+    path: String,
+    messages: template::Shared,
+    params: Vec<param::Shared>,
     menu: ui::menu::Menu,
+    sequencer: Option<Sequencer>,
+    modulation: Modulation,
+    automations: Vec<automation::Shared>,
+    running: Arc<AtomicBool>,
 }
 
 impl Patch {
-    pub fn new(name: &str, menu: ui::menu::Menu) -> Self {
+    pub fn new(
+        path: &str,
+        messages: template::Shared,
+        params: Vec<param::Shared>,
+        menu: ui::menu::Menu,
+        sequencer: Option<Sequencer>,
+        modulation: Modulation,
+        automations: Vec<automation::Shared>,
+        running: Arc<AtomicBool>,
+    ) -> Self {
         Patch {
-            name: name.to_string(),
+            path: path.to_string(),
+            messages: messages,
+            params: params,
             menu: menu,
+            sequencer: sequencer,
+            modulation: modulation,
+            automations: automations,
+            running: running,
         }
     }
 
-    pub fn start(&self) {
-        let res = osc::send("start", Some(vec![osc::Type::String(self.name.clone())]));
+    // keep the param values in the patch's preset for next time
+    fn save(&self) {
+        let mut preset = preset::load(&self.path).unwrap_or_default();
+        preset.params = self.params.iter().map(|param| {
+            let param = param.lock().unwrap();
+            (param.name.clone(), param.value)
+        }).collect();
 
-        if let Err(err) = res {
+        if let Err(err) = preset::save(&self.path, &preset) {
+            println!("error saving preset: {}", err);
+        }
+    }
+
+    pub fn start(&mut self) {
+        // start the patch and bring it up to date with the current param
+        // values all at once
+        let mut bundle = osc::Bundle::new();
+        self.messages.start(&mut bundle);
+
+        for param in self.params.iter() {
+            param.lock().unwrap().add(&mut bundle);
+        }
+
+        let conf = &*config::CONFIG.get();
+        if let Err(err) = bundle.send(conf.osc.latency()) {
             println!("error sending OSC message: {}", err);
         }
+
+        if let Some(sequencer) = &mut self.sequencer {
+            sequencer.start();
+        }
+
+        self.modulation.start();
+
+        for automation in self.automations.iter() {
+            automation.lock().unwrap().play();
+        }
+
+        self.running.store(true, Ordering::SeqCst);
     }
 
-    pub fn stop(&self) {
-        let res = osc::send("stop", None);
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
 
-        if let Err(err) = res {
+        for automation in self.automations.iter() {
+            automation.lock().unwrap().stop();
+        }
+
+        self.modulation.stop();
+
+        if let Some(sequencer) = &mut self.sequencer {
+            sequencer.stop();
+        }
+
+        let mut bundle = osc::Bundle::new();
+        self.messages.stop(&mut bundle);
+
+        if let Err(err) = bundle.send(None) {
             println!("error sending OSC message: {}", err);
         }
+
+        self.save();
     }
 }
 
@@ -42,6 +121,14 @@ impl ui::Screen for Patch {
         self.menu.handle(input)
     }
 
+    fn next_tick(&self) -> Option<Duration> {
+        self.menu.next_tick()
+    }
+
+    fn tick(&mut self) -> bool {
+        self.menu.tick()
+    }
+
     fn load(&mut self) {
         self.start();
     }
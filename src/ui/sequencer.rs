@@ -0,0 +1,183 @@
+use crate::preset;
+use crate::sequencer::Pattern;
+use crate::ui;
+use raqote;
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// how often to check whether the playhead moved
+const REFRESH: Duration = Duration::from_millis(20);
+
+// space between the label's baseline and the grid
+const GRID_GAP: f32 = 4.0;
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Navigate,
+    Edit,
+}
+
+#[derive(Debug)]
+pub struct Sequencer {
+    patch: String,
+    pattern: Arc<Mutex<Pattern>>,
+    cursor: usize,
+    mode: Mode,
+    // the playhead position that was last rendered
+    shown: Cell<Option<usize>>,
+}
+
+impl Sequencer {
+    pub fn new(patch: &str, pattern: Arc<Mutex<Pattern>>) -> Self {
+        Sequencer {
+            patch: patch.to_string(),
+            pattern: pattern,
+            cursor: 0,
+            mode: Mode::Navigate,
+            shown: Cell::new(None),
+        }
+    }
+
+    // the cursor walks the grid row by row, with one extra position
+    // past the last cell for going back
+    fn cells(&self, pattern: &Pattern) -> usize {
+        pattern.tracks.len() * pattern.steps
+    }
+
+    fn cell(&self, pattern: &Pattern) -> Option<(usize, usize)> {
+        if self.cursor < self.cells(pattern) {
+            Some((self.cursor / pattern.steps, self.cursor % pattern.steps))
+        } else {
+            None
+        }
+    }
+
+    pub fn save(&self) {
+        let pattern = self.pattern.lock().unwrap();
+
+        let mut preset = preset::load(&self.patch).unwrap_or_default();
+        preset.pattern = Some(pattern.clone());
+
+        if let Err(err) = preset::save(&self.patch, &preset) {
+            println!("error saving preset: {}", err);
+        }
+    }
+}
+
+impl ui::Screen for Sequencer {
+    fn render(&self, target: &mut raqote::DrawTarget) {
+        let pattern = self.pattern.lock().unwrap();
+        self.shown.set(pattern.position);
+
+        let label = match self.cell(&pattern) {
+            Some((track, step)) => {
+                let track = &pattern.tracks[track];
+                let marker = if self.mode == Mode::Edit { ">" } else { " " };
+
+                if track.gates[step] {
+                    format!("{}{} {}", marker, track.name, track.display(step))
+                } else {
+                    format!("{}{} --", marker, track.name)
+                }
+            },
+            None => "<-".to_string(),
+        };
+
+        ui::render_lines(vec![label], target);
+
+        let rows = pattern.tracks.len().max(1) as f32;
+        let cell_width = target.width() as f32 / pattern.steps as f32;
+        let grid_top = ui::line_y(0) + GRID_GAP;
+        let cell_height = ((target.height() as f32 - grid_top) / rows).min(cell_width);
+        let cursor = self.cell(&pattern);
+
+        let draw_options = raqote::DrawOptions::new();
+        let stroke_style = raqote::StrokeStyle::default();
+
+        for (i, track) in pattern.tracks.iter().enumerate() {
+            for step in 0..pattern.steps {
+                let x = step as f32 * cell_width;
+                let y = grid_top + i as f32 * cell_height;
+
+                let mut pb = raqote::PathBuilder::new();
+                if track.gates[step] {
+                    pb.rect(x + 1.0, y + 1.0, cell_width - 2.0, cell_height - 2.0);
+                    target.fill(&pb.finish(), &ui::FOREGROUND_SOURCE, &draw_options);
+                } else {
+                    pb.rect(x + 1.5, y + 1.5, cell_width - 3.0, cell_height - 3.0);
+                    target.stroke(&pb.finish(), &ui::FOREGROUND_SOURCE, &stroke_style, &draw_options);
+                }
+
+                if cursor == Some((i, step)) {
+                    let mut pb = raqote::PathBuilder::new();
+                    pb.rect(x + 0.5, y + cell_height, cell_width - 1.0, 1.0);
+                    target.fill(&pb.finish(), &ui::FOREGROUND_SOURCE, &draw_options);
+                }
+            }
+        }
+
+        if let Some(position) = pattern.position {
+            let mut pb = raqote::PathBuilder::new();
+            pb.rect(position as f32 * cell_width, grid_top - 3.0, cell_width, 2.0);
+            target.fill(&pb.finish(), &ui::FOREGROUND_SOURCE, &draw_options);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        Some(REFRESH)
+    }
+
+    fn tick(&mut self) -> bool {
+        self.pattern.lock().unwrap().position != self.shown.get()
+    }
+
+    fn handle(&mut self, input: ui::Input) -> Option<ui::Action> {
+        let mut pattern = self.pattern.lock().unwrap();
+        let cell = self.cell(&pattern);
+
+        match self.mode {
+            Mode::Navigate => match input {
+                ui::Input::Left => {
+                    if self.cursor > 0 {
+                        self.cursor -= 1;
+                    }
+                    None
+                },
+                ui::Input::Right => {
+                    if self.cursor < self.cells(&pattern) {
+                        self.cursor += 1;
+                    }
+                    None
+                },
+                ui::Input::Press => {
+                    match cell {
+                        Some(_) => {
+                            self.mode = Mode::Edit;
+                            None
+                        },
+                        None => Some(ui::Action::Pop),
+                    }
+                },
+                ui::Input::LongPress | ui::Input::Down | ui::Input::Release => None,
+            },
+            Mode::Edit => {
+                let (track, step) = cell.unwrap();
+
+                match input {
+                    ui::Input::Left => pattern.tracks[track].dec(step),
+                    ui::Input::Right => pattern.tracks[track].inc(step),
+                    ui::Input::Press => self.mode = Mode::Navigate,
+                    ui::Input::LongPress | ui::Input::Down | ui::Input::Release => {},
+                }
+
+                None
+            },
+        }
+    }
+
+    fn unload(&mut self) {
+        self.mode = Mode::Navigate;
+        self.save();
+    }
+}
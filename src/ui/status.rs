@@ -0,0 +1,92 @@
+use crate::config;
+use crate::server;
+use crate::status;
+use crate::synthdefs;
+use crate::ui;
+use raqote;
+use std::cell::Cell;
+use std::time::Duration;
+
+// the server's load keeps changing, so it's rendered again every so often
+const REFRESH: Duration = Duration::from_secs(1);
+
+// lines that don't fit on the screen are scrolled to with the encoder
+#[derive(Debug)]
+pub struct Status {
+    offset: usize,
+    visible: Cell<usize>,
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Status {
+            offset: 0,
+            visible: Cell::new(1),
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let health = status::health();
+
+        let state = match &health {
+            Some(health) if health.answering => "up",
+            Some(_) => "down",
+            None => "not monitored",
+        };
+
+        let mut lines = vec![format!("server: {}", state)];
+        let status = health.and_then(|health| health.status);
+
+        if let Some(status) = &status {
+            lines.push(format!("cpu {:.1}% {:.1}%", status.avg_cpu, status.peak_cpu));
+            lines.push(format!("synths {}", status.synths));
+        }
+
+        if let Some(state) = server::current() {
+            lines.push(format!("proc: {}", state.display()));
+        }
+
+        let conf = &*config::CONFIG.get();
+        if conf.server.as_ref().map_or(false, |server| !server.synthdefs.is_empty()) {
+            let state = if synthdefs::is_loaded() { "loaded" } else { "loading" };
+            lines.push(format!("defs: {}", state));
+        }
+
+        if let Some(status) = &status {
+            lines.push(format!("ugens {}", status.ugens));
+            lines.push(format!("sr {:.0}", status.actual_sample_rate));
+        }
+
+        lines
+    }
+}
+
+impl ui::Screen for Status {
+    fn render(&self, target: &mut raqote::DrawTarget) {
+        let lines = self.lines();
+        self.visible.set(ui::visible_lines(target));
+        let offset = self.offset.min(lines.len().saturating_sub(self.visible.get()));
+
+        ui::render_lines(lines[offset..].to_vec(), target);
+    }
+
+    fn next_tick(&self) -> Option<Duration> {
+        Some(REFRESH)
+    }
+
+    fn handle(&mut self, input: ui::Input) -> Option<ui::Action> {
+        match input {
+            ui::Input::Left => {
+                self.offset = self.offset.saturating_sub(1);
+                None
+            },
+            ui::Input::Right => {
+                let max = self.lines().len().saturating_sub(self.visible.get());
+                self.offset = (self.offset + 1).min(max);
+                None
+            },
+            ui::Input::Press => Some(ui::Action::Pop),
+            _ => None,
+        }
+    }
+}